@@ -8,7 +8,7 @@ fn benchmark_find_label(c: &mut Criterion) {
     for i in 0..25 {
         // random add either a label or a random instruction
         if i % 2 == 0 {
-            program.add_instruction(Instruction::HLT);
+            program.add_instruction(Instruction::HLT(None));
         } else if i % 3 == 0 {
             program.add_instruction(Instruction::JMP(Label { name: i }));
         } else {
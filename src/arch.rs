@@ -0,0 +1,659 @@
+use crate::enums::{
+    u2, FloatParameter, FloatRegister, Instruction, Label, OutputFormat, Parameter, Register,
+    Variable, REGISTERS,
+};
+use crate::file::LineCategory;
+use std::fmt;
+use std::str::FromStr;
+
+// machine a program is written for, chosen by the `#CONFIG` line of the source and
+// stored in the first word of a binary program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Architecture {
+    // number of registers, T0 to T15 at most
+    pub registers: u8,
+    // number of memory words, a power of two
+    pub memory: u32,
+    // bits of a register, a memory word and a stack value
+    pub word: u32,
+    // maximum number of values on the stack, None when it is unlimited
+    pub stack: Option<usize>,
+}
+
+// the machine of the programs without `#CONFIG`, whose instructions have 32 bits
+pub const CLASSIC: Architecture = Architecture {
+    registers: 4,
+    memory: 1024,
+    word: 32,
+    stack: None,
+};
+
+// a larger machine using the 64-bit encoding
+pub const WIDE: Architecture = Architecture {
+    registers: 16,
+    memory: 65536,
+    word: 32,
+    stack: None,
+};
+
+// a small machine, for the exercises on overflows and stack limits
+pub const SMALL: Architecture = Architecture {
+    registers: 4,
+    memory: 256,
+    word: 8,
+    stack: Some(16),
+};
+
+// the classic machine with 64-bit registers, memory cells and stack values, keeping
+// the 32-bit encoding of the instructions
+pub const CLASSIC64: Architecture = Architecture {
+    registers: 4,
+    memory: 1024,
+    word: 64,
+    stack: None,
+};
+
+const PROFILES: [(&str, Architecture); 4] = [
+    ("classic", CLASSIC),
+    ("wide", WIDE),
+    ("small", SMALL),
+    ("classic64", CLASSIC64),
+];
+
+// word sizes, in the order of their code in the header
+const WORDS: [u32; 4] = [8, 16, 32, 64];
+
+// largest stack size that fits in the header
+const MAX_STACK: usize = 0b11111111111111;
+
+impl Default for Architecture {
+    fn default() -> Self {
+        CLASSIC
+    }
+}
+
+impl Architecture {
+    // the instructions take 64 bits when the registers or the addresses do not fit
+    // in the 32-bit encoding
+    pub fn is_wide(&self) -> bool {
+        self.registers > 4 || self.memory > 1024
+    }
+
+    // bits of a constant operand and of a #DATA value in the encoding, no more than
+    // the bits of a word of the machine
+    pub fn value_bits(&self) -> u32 {
+        let bits = if self.is_wide() { 16 } else { 10 };
+        bits.min(self.word)
+    }
+
+    // largest constant operand or #DATA value
+    pub fn max_value(&self) -> u32 {
+        (1 << self.value_bits()) - 1
+    }
+
+    pub fn word_mask(&self) -> u64 {
+        u64::MAX >> (64 - self.word)
+    }
+
+    // value of a word read as a signed number
+    pub fn sign_extend(&self, value: u64) -> i64 {
+        let shift = 64 - self.word;
+        ((value << shift) as i64) >> shift
+    }
+
+    // bits kept from a #DATA value: the ones of the encoding, or the whole word of a
+    // 64-bit machine, whose values can only be written in full in the source
+    pub fn data_mask(&self) -> u64 {
+        match self.word {
+            64 => u64::MAX,
+            _ => self.max_value() as u64,
+        }
+    }
+
+    pub fn registers(&self) -> &'static [Register] {
+        &REGISTERS[..self.registers as usize]
+    }
+
+    // check that the registers and the addresses of an instruction exist on the machine
+    pub fn check(&self, instruction: &Instruction) -> Result<(), String> {
+        let mut registers = instruction.registers_read();
+        registers.extend(instruction.register_written());
+        if let Instruction::POP(register) = instruction {
+            registers.push(*register);
+        }
+        for register in registers {
+            if register as u8 >= self.registers {
+                return Err(format!(
+                    "{} does not exist, the machine has {} registers",
+                    register, self.registers
+                ));
+            }
+        }
+        let end = match instruction {
+            Instruction::VARIABLE(variable, _) => variable.name as u32 + 1,
+            Instruction::ARRAY(variable, _, length) => variable.name as u32 + *length as u32,
+            _ => match instruction.variables().iter().map(|v| v.name).max() {
+                Some(address) => address as u32 + 1,
+                None => 0,
+            },
+        };
+        if end > self.memory {
+            return Err(format!(
+                "Too many variables, the memory has {} words",
+                self.memory
+            ));
+        }
+        // a memory word holds the 32 bits of a f32
+        if instruction.is_float() && self.word < 32 {
+            return Err(format!(
+                "{} needs words of at least 32 bits, the machine has {}-bit words",
+                instruction.mnemonic(),
+                self.word
+            ));
+        }
+        Ok(())
+    }
+
+    // check that an instruction fits in the encoding of a binary program, whose labels
+    // and #DATA values have fewer bits than the ones of a program run from its source
+    pub fn check_encoding(&self, instruction: &Instruction) -> Result<(), String> {
+        // a 64-bit machine keeps the whole #DATA values, which the encoding cannot hold
+        if let Instruction::VARIABLE(_, value) | Instruction::ARRAY(_, value, _) = instruction {
+            if value & self.data_mask() > self.max_value() as u64 {
+                return Err(format!(
+                    "#DATA value {} does not fit in the {} bits of the encoding",
+                    value,
+                    self.value_bits()
+                ));
+            }
+        }
+        // the classic encoding has 3 bits for a label, the wide one 16
+        let label = match instruction {
+            Instruction::LABEL(label) => Some(*label),
+            _ => instruction.target(),
+        };
+        let labels = if self.is_wide() { 1 << 16 } else { 1 << 3 };
+        if label.is_some_and(|l| l.name as u32 >= labels) {
+            return Err(format!(
+                "Too many labels, the encoding of the machine holds {}",
+                labels
+            ));
+        }
+        Ok(())
+    }
+
+    // first word of a binary program for this machine: the category marker, 0b10,
+    // the number of registers minus one (4 bits), the log2 of the memory size (5 bits),
+    // the code of the word size (2 bits) and the stack size (14 bits, 0 when unlimited)
+    pub fn header(&self) -> u32 {
+        let mut res: u32 = 0b11111;
+        res = res << 2 | 0b10;
+        res = res << 4 | (self.registers as u32 - 1);
+        res = res << 5 | self.memory.trailing_zeros();
+        res = res << 2 | WORDS.iter().position(|w| *w == self.word).unwrap() as u32;
+        res = res << 14 | self.stack.unwrap_or(0) as u32;
+        res
+    }
+
+    pub fn is_header(word: u32) -> bool {
+        word >> 25 == 0b1111110
+    }
+
+    pub fn from_header(word: u32) -> Result<Architecture, String> {
+        let word_code = ((word >> 14) & 0b11) as usize;
+        let architecture = Architecture {
+            registers: ((word >> 21) & 0b1111) as u8 + 1,
+            memory: 1 << ((word >> 16) & 0b11111),
+            word: *WORDS
+                .get(word_code)
+                .ok_or("invalid word size in the header")?,
+            stack: match (word & 0b11111111111111) as usize {
+                0 => None,
+                size => Some(size),
+            },
+        };
+        architecture.validate()?;
+        Ok(architecture)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !(1..=16).contains(&self.registers) {
+            return Err(format!(
+                "{} registers, the machine has 1 to 16 registers",
+                self.registers
+            ));
+        }
+        if !self.memory.is_power_of_two() || !(16..=65536).contains(&self.memory) {
+            return Err(format!(
+                "{} words of memory, the memory is a power of two from 16 to 65536 words",
+                self.memory
+            ));
+        }
+        if !WORDS.contains(&self.word) {
+            return Err(format!(
+                "{}-bit words, the words have 8, 16, 32 or 64 bits",
+                self.word
+            ));
+        }
+        if self.stack.is_some_and(|s| s == 0 || s > MAX_STACK) {
+            return Err(format!(
+                "stack of {} values, the stack holds 1 to {} values",
+                self.stack.unwrap(),
+                MAX_STACK
+            ));
+        }
+        Ok(())
+    }
+
+    // the architecture given by a `#CONFIG` line, None for the other lines
+    pub fn from_directive(line: &str) -> Option<Result<Architecture, String>> {
+        let options = line
+            .split(';')
+            .next()
+            .unwrap()
+            .trim()
+            .strip_prefix("#CONFIG")?;
+        Some(Architecture::from_str(options))
+    }
+}
+
+// a profile name followed by `key=value` options changing it, separated by spaces or
+// commas: `wide`, `classic stack=64`, `registers=8,memory=4096`
+impl FromStr for Architecture {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut architecture = CLASSIC;
+        let words = s.split([' ', '\t', ',']).filter(|w| !w.is_empty());
+        for (i, word) in words.enumerate() {
+            let (key, value) = match word.split_once('=') {
+                Some(option) => option,
+                None if i == 0 => {
+                    architecture = match PROFILES.iter().find(|(name, _)| *name == word) {
+                        Some((_, profile)) => *profile,
+                        None => return Err(format!("unknown profile {}", word)),
+                    };
+                    continue;
+                }
+                None => return Err(format!("expected <key>=<value>, found {}", word)),
+            };
+            let number = value
+                .parse::<u32>()
+                .map_err(|_| format!("invalid value {} for {}", value, key))?;
+            match key {
+                "registers" => architecture.registers = number.min(255) as u8,
+                "memory" => architecture.memory = number,
+                "word" => architecture.word = number,
+                "stack" if number == 0 => architecture.stack = None,
+                "stack" => architecture.stack = Some(number as usize),
+                _ => return Err(format!("unknown option {}", key)),
+            }
+        }
+        architecture.validate()?;
+        Ok(architecture)
+    }
+}
+
+impl fmt::Display for Architecture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((name, _)) = PROFILES.iter().find(|(_, p)| p == self) {
+            return write!(f, "{}", name);
+        }
+        write!(
+            f,
+            "registers={} memory={} word={} stack={}",
+            self.registers,
+            self.memory,
+            self.word,
+            self.stack.unwrap_or(0)
+        )
+    }
+}
+
+// bits of a 64-bit instruction, filled and read from the most significant one
+struct Fields {
+    word: u64,
+    used: u32,
+}
+
+impl Fields {
+    fn push(&mut self, value: u64, width: u32) {
+        self.used += width;
+        self.word |= (value & ((1 << width) - 1)) << (64 - self.used);
+    }
+
+    fn take(&mut self, width: u32) -> u64 {
+        self.used += width;
+        (self.word >> (64 - self.used)) & ((1 << width) - 1)
+    }
+
+    // 2 bits for the type and 16 bits for the register number, the address or the constant
+    fn push_parameter(&mut self, parameter: &Parameter) {
+        let (kind, value) = match parameter {
+            Parameter::Register(r) => (0b00, *r as u64),
+            Parameter::Variable(v) => (0b01, v.name as u64),
+            Parameter::Constant(c) => (0b10, *c as u64),
+        };
+        self.push(kind, 2);
+        self.push(value, 16);
+    }
+
+    fn take_parameter(&mut self) -> Result<Parameter, String> {
+        let kind = self.take(2);
+        let value = self.take(16);
+        match kind {
+            0b00 => match u2::try_from(value) {
+                Ok(register) => Ok(Parameter::Register(Register::try_from(register)?)),
+                Err(_) => Err("Invalid register".to_string()),
+            },
+            0b01 => Ok(Parameter::Variable(Variable::from(value as u16))),
+            0b10 => Ok(Parameter::Constant(value as u32)),
+            _ => Err("Invalid parameter".to_string()),
+        }
+    }
+
+    fn take_register(&mut self) -> Result<Register, String> {
+        Register::try_from(self.take(4) as u2)
+    }
+
+    fn take_float_register(&mut self) -> Result<FloatRegister, String> {
+        FloatRegister::try_from(self.take(4) as u2)
+    }
+
+    fn take_float_parameter(&mut self) -> Result<FloatParameter, String> {
+        FloatParameter::try_from(self.take_parameter()?)
+    }
+}
+
+// 64-bit word of an instruction of a wide machine: the opcodes are the ones of the
+// 32-bit encoding, with 4 bits for the registers and 16 bits for the addresses, the
+// labels and the values
+pub fn encode_wide(instruction: &Instruction) -> u64 {
+    let mut fields = Fields { word: 0, used: 0 };
+    let opcode = |fields: &mut Fields, opcode: u64| fields.push(opcode, 5);
+    match instruction {
+        Instruction::VARIABLE(v, i) => {
+            fields.push(0b0, 1);
+            fields.push(v.name as u64, 16);
+            fields.push(*i, 16);
+        }
+        Instruction::ARRAY(v, i, l) => {
+            fields.push(0b1, 1);
+            fields.push(v.name as u64, 16);
+            fields.push(*l as u64, 16);
+            fields.push(*i, 16);
+        }
+        Instruction::LDA(r, p)
+        | Instruction::AND(r, p)
+        | Instruction::OR(r, p)
+        | Instruction::ADD(r, p)
+        | Instruction::SUB(r, p)
+        | Instruction::DIV(r, p)
+        | Instruction::MUL(r, p)
+        | Instruction::MOD(r, p) => {
+            let code = match instruction {
+                Instruction::LDA(_, _) => 0b00000,
+                Instruction::AND(_, _) => 0b00100,
+                Instruction::OR(_, _) => 0b00101,
+                Instruction::ADD(_, _) => 0b00111,
+                Instruction::SUB(_, _) => 0b01000,
+                Instruction::DIV(_, _) => 0b01001,
+                Instruction::MUL(_, _) => 0b01010,
+                _ => 0b01011,
+            };
+            opcode(&mut fields, code);
+            fields.push(*r as u64, 4);
+            fields.push_parameter(p);
+        }
+        Instruction::STR(v, p) => {
+            opcode(&mut fields, 0b00001);
+            fields.push(v.name as u64, 16);
+            fields.push_parameter(p);
+        }
+        Instruction::PUSH(p) => {
+            opcode(&mut fields, 0b00010);
+            fields.push_parameter(p);
+        }
+        Instruction::POP(r) | Instruction::NOT(r) | Instruction::INC(r) | Instruction::DEC(r) => {
+            let code = match instruction {
+                Instruction::POP(_) => 0b00011,
+                Instruction::NOT(_) => 0b00110,
+                Instruction::INC(_) => 0b01100,
+                _ => 0b01101,
+            };
+            opcode(&mut fields, code);
+            fields.push(*r as u64, 4);
+        }
+        Instruction::BEQ(p1, p2, l)
+        | Instruction::BNE(p1, p2, l)
+        | Instruction::BSM(p1, p2, l)
+        | Instruction::BBG(p1, p2, l) => {
+            let code = match instruction {
+                Instruction::BEQ(_, _, _) => 0b01110,
+                Instruction::BNE(_, _, _) => 0b01111,
+                Instruction::BSM(_, _, _) => 0b10000,
+                _ => 0b10001,
+            };
+            opcode(&mut fields, code);
+            fields.push_parameter(p1);
+            fields.push_parameter(p2);
+            fields.push(l.name as u64, 16);
+        }
+        Instruction::JMP(l) | Instruction::LABEL(l) => {
+            let code = match instruction {
+                Instruction::JMP(_) => 0b10010,
+                _ => 0b11110,
+            };
+            opcode(&mut fields, code);
+            fields.push(l.name as u64, 16);
+        }
+        Instruction::SRL(r, c) | Instruction::SRR(r, c) => {
+            let code = match instruction {
+                Instruction::SRL(_, _) => 0b10011,
+                _ => 0b10100,
+            };
+            opcode(&mut fields, code);
+            fields.push(*r as u64, 4);
+            fields.push(*c as u64, 16);
+        }
+        Instruction::HLT(p) => {
+            opcode(&mut fields, 0b10101);
+            fields.push(p.is_some() as u64, 1);
+            if let Some(p) = p {
+                fields.push_parameter(p);
+            }
+        }
+        // the mode of IN and OUT follows the parameter, as in the 32-bit encoding
+        Instruction::IN(p) | Instruction::GETC(p) => {
+            opcode(&mut fields, 0b10110);
+            fields.push_parameter(p);
+            fields.push(matches!(instruction, Instruction::GETC(_)) as u64, 3);
+        }
+        Instruction::OUT(p, format) => {
+            opcode(&mut fields, 0b10111);
+            fields.push_parameter(p);
+            fields.push(Into::<u32>::into(*format) as u64, 3);
+        }
+        Instruction::PRINTS(v) => {
+            opcode(&mut fields, 0b10111);
+            fields.push_parameter(&Parameter::Variable(*v));
+            fields.push(0b010, 3);
+        }
+        // the operation, the condition and the direction of the FPU instructions come
+        // after the opcode, as in the 32-bit encoding
+        Instruction::FLDA(r, p) => {
+            opcode(&mut fields, 0b11000);
+            fields.push(*r as u64, 4);
+            fields.push_parameter(&Parameter::from(*p));
+        }
+        Instruction::FSTR(v, r) => {
+            opcode(&mut fields, 0b11001);
+            fields.push(v.name as u64, 16);
+            fields.push(*r as u64, 4);
+        }
+        Instruction::FADD(r, p)
+        | Instruction::FSUB(r, p)
+        | Instruction::FMUL(r, p)
+        | Instruction::FDIV(r, p) => {
+            let operation = match instruction {
+                Instruction::FADD(_, _) => 0b00,
+                Instruction::FSUB(_, _) => 0b01,
+                Instruction::FMUL(_, _) => 0b10,
+                _ => 0b11,
+            };
+            opcode(&mut fields, 0b11010);
+            fields.push(operation, 2);
+            fields.push(*r as u64, 4);
+            fields.push_parameter(&Parameter::from(*p));
+        }
+        Instruction::FBEQ(r, p, l)
+        | Instruction::FBNE(r, p, l)
+        | Instruction::FBSM(r, p, l)
+        | Instruction::FBBG(r, p, l) => {
+            let condition = match instruction {
+                Instruction::FBEQ(_, _, _) => 0b00,
+                Instruction::FBNE(_, _, _) => 0b01,
+                Instruction::FBSM(_, _, _) => 0b10,
+                _ => 0b11,
+            };
+            opcode(&mut fields, 0b11011);
+            fields.push(condition, 2);
+            fields.push(*r as u64, 4);
+            fields.push_parameter(&Parameter::from(*p));
+            fields.push(l.name as u64, 16);
+        }
+        Instruction::ITOF(r, p) => {
+            opcode(&mut fields, 0b11100);
+            fields.push(0b0, 1);
+            fields.push(*r as u64, 4);
+            fields.push_parameter(p);
+        }
+        Instruction::FTOI(r, s) => {
+            opcode(&mut fields, 0b11100);
+            fields.push(0b1, 1);
+            fields.push(*r as u64, 4);
+            fields.push(*s as u64, 4);
+        }
+        Instruction::FOUT(p) => {
+            opcode(&mut fields, 0b11101);
+            fields.push_parameter(&Parameter::from(*p));
+        }
+    }
+    fields.word
+}
+
+// instruction of a 64-bit word written by encode_wide
+pub fn decode_wide(word: u64, category: &LineCategory) -> Result<Instruction, String> {
+    let mut fields = Fields { word, used: 0 };
+    if *category == LineCategory::DATA {
+        let array = fields.take(1) == 1;
+        let name = Variable::from(fields.take(16) as u16);
+        if !array {
+            return Ok(Instruction::VARIABLE(name, fields.take(16)));
+        }
+        let length = fields.take(16) as u16;
+        return Ok(Instruction::ARRAY(name, fields.take(16), length));
+    }
+    let instruction = match fields.take(5) {
+        0b00000 => Instruction::LDA(fields.take_register()?, fields.take_parameter()?),
+        0b00001 => {
+            let variable = Variable::from(fields.take(16) as u16);
+            Instruction::STR(variable, fields.take_parameter()?)
+        }
+        0b00010 => Instruction::PUSH(fields.take_parameter()?),
+        0b00011 => Instruction::POP(fields.take_register()?),
+        0b00100 => Instruction::AND(fields.take_register()?, fields.take_parameter()?),
+        0b00101 => Instruction::OR(fields.take_register()?, fields.take_parameter()?),
+        0b00110 => Instruction::NOT(fields.take_register()?),
+        0b00111 => Instruction::ADD(fields.take_register()?, fields.take_parameter()?),
+        0b01000 => Instruction::SUB(fields.take_register()?, fields.take_parameter()?),
+        0b01001 => Instruction::DIV(fields.take_register()?, fields.take_parameter()?),
+        0b01010 => Instruction::MUL(fields.take_register()?, fields.take_parameter()?),
+        0b01011 => Instruction::MOD(fields.take_register()?, fields.take_parameter()?),
+        0b01100 => Instruction::INC(fields.take_register()?),
+        0b01101 => Instruction::DEC(fields.take_register()?),
+        opcode @ 0b01110..=0b10001 => {
+            let p1 = fields.take_parameter()?;
+            let p2 = fields.take_parameter()?;
+            let label = Label {
+                name: fields.take(16) as u16,
+            };
+            match opcode {
+                0b01110 => Instruction::BEQ(p1, p2, label),
+                0b01111 => Instruction::BNE(p1, p2, label),
+                0b10000 => Instruction::BSM(p1, p2, label),
+                _ => Instruction::BBG(p1, p2, label),
+            }
+        }
+        0b10010 => Instruction::JMP(Label {
+            name: fields.take(16) as u16,
+        }),
+        0b10011 => Instruction::SRL(fields.take_register()?, fields.take(16) as u16),
+        0b10100 => Instruction::SRR(fields.take_register()?, fields.take(16) as u16),
+        0b10101 => match fields.take(1) {
+            0 => Instruction::HLT(None),
+            _ => Instruction::HLT(Some(fields.take_parameter()?)),
+        },
+        0b10110 => {
+            let parameter = fields.take_parameter()?;
+            match fields.take(3) {
+                0b000 => Instruction::IN(parameter),
+                0b001 => Instruction::GETC(parameter),
+                _ => return Err("Invalid input mode".to_string()),
+            }
+        }
+        0b10111 => {
+            let parameter = fields.take_parameter()?;
+            match (fields.take(3), parameter) {
+                (0b010, Parameter::Variable(variable)) => Instruction::PRINTS(variable),
+                (0b010, _) => return Err("Invalid string address".to_string()),
+                (mode, parameter) => {
+                    Instruction::OUT(parameter, OutputFormat::try_from(mode as u32)?)
+                }
+            }
+        }
+        0b11000 => Instruction::FLDA(
+            fields.take_float_register()?,
+            fields.take_float_parameter()?,
+        ),
+        0b11001 => {
+            let variable = Variable::from(fields.take(16) as u16);
+            Instruction::FSTR(variable, fields.take_float_register()?)
+        }
+        0b11010 => {
+            let operation = fields.take(2);
+            let register = fields.take_float_register()?;
+            let parameter = fields.take_float_parameter()?;
+            match operation {
+                0b00 => Instruction::FADD(register, parameter),
+                0b01 => Instruction::FSUB(register, parameter),
+                0b10 => Instruction::FMUL(register, parameter),
+                _ => Instruction::FDIV(register, parameter),
+            }
+        }
+        0b11011 => {
+            let condition = fields.take(2);
+            let register = fields.take_float_register()?;
+            let parameter = fields.take_float_parameter()?;
+            let label = Label {
+                name: fields.take(16) as u16,
+            };
+            match condition {
+                0b00 => Instruction::FBEQ(register, parameter, label),
+                0b01 => Instruction::FBNE(register, parameter, label),
+                0b10 => Instruction::FBSM(register, parameter, label),
+                _ => Instruction::FBBG(register, parameter, label),
+            }
+        }
+        0b11100 => match fields.take(1) {
+            0 => Instruction::ITOF(fields.take_float_register()?, fields.take_parameter()?),
+            _ => Instruction::FTOI(fields.take_register()?, fields.take_float_register()?),
+        },
+        0b11101 => Instruction::FOUT(fields.take_float_parameter()?),
+        0b11110 => Instruction::LABEL(Label {
+            name: fields.take(16) as u16,
+        }),
+        _ => return Err("Invalid instruction".to_string()),
+    };
+    Ok(instruction)
+}
@@ -0,0 +1,210 @@
+use crate::enums::Instruction;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Replacement {
+    Lru,
+    Fifo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WritePolicy {
+    // writes stay in the cache until the line is evicted
+    WriteBack,
+    // writes go to memory, a write miss does not load the line
+    WriteThrough,
+}
+
+// geometry and policies of the data cache, sizes are in memory words
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub size: u32,
+    pub line_size: u32,
+    pub associativity: u32,
+    pub replacement: Replacement,
+    pub write_policy: WritePolicy,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            size: 64,
+            line_size: 4,
+            associativity: 1,
+            replacement: Replacement::Lru,
+            write_policy: WritePolicy::WriteBack,
+        }
+    }
+}
+
+// parse a comma separated list of `key=value` such as `size=64,line=4,ways=2,policy=lru,write=back`
+impl FromStr for CacheConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = CacheConfig::default();
+        for option in s.split(',').filter(|o| !o.is_empty()) {
+            let (key, value) = match option.split_once('=') {
+                Some(pair) => pair,
+                None => return Err(format!("expected key=value, got {}", option)),
+            };
+            let number = || {
+                value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or(format!("invalid value for {}: {}", key, value))
+            };
+            match key {
+                "size" => config.size = number()?,
+                "line" => config.line_size = number()?,
+                "ways" => config.associativity = number()?,
+                "policy" => {
+                    config.replacement = match value {
+                        "lru" => Replacement::Lru,
+                        "fifo" => Replacement::Fifo,
+                        _ => return Err(format!("unknown replacement policy {}", value)),
+                    }
+                }
+                "write" => {
+                    config.write_policy = match value {
+                        "back" => WritePolicy::WriteBack,
+                        "through" => WritePolicy::WriteThrough,
+                        _ => return Err(format!("unknown write policy {}", value)),
+                    }
+                }
+                _ => return Err(format!("unknown cache option {}", key)),
+            }
+        }
+        if config.size % (config.line_size * config.associativity) != 0 {
+            return Err("size must be a multiple of line size times associativity".to_string());
+        }
+        Ok(config)
+    }
+}
+
+struct Line {
+    tag: u32,
+    dirty: bool,
+    loaded: u64,
+    used: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    // dirty lines written back to memory on eviction
+    pub writebacks: u64,
+    // writes sent directly to memory in write-through mode
+    pub memory_writes: u64,
+    // hits and misses of each instruction, by index in the program
+    pub per_instruction: BTreeMap<usize, (u64, u64)>,
+}
+
+impl CacheStats {
+    pub fn miss_rate(&self) -> f64 {
+        if self.hits + self.misses == 0 {
+            return 0.0;
+        }
+        self.misses as f64 / (self.hits + self.misses) as f64
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Cache hits: {}", self.hits)?;
+        writeln!(f, "Cache misses: {}", self.misses)?;
+        writeln!(f, "Miss rate: {:.2}%", self.miss_rate() * 100.0)?;
+        writeln!(f, "Write-backs: {}", self.writebacks)?;
+        writeln!(f, "Memory writes: {}", self.memory_writes)
+    }
+}
+
+// data cache observing the accesses to the memory of a program
+pub struct Cache {
+    pub config: CacheConfig,
+    pub stats: CacheStats,
+    sets: Vec<Vec<Line>>,
+    clock: u64,
+}
+
+impl Cache {
+    pub fn new(config: CacheConfig) -> Cache {
+        let sets = config.size / (config.line_size * config.associativity);
+        Cache {
+            sets: (0..sets).map(|_| Vec::new()).collect(),
+            config,
+            stats: CacheStats::default(),
+            clock: 0,
+        }
+    }
+
+    // record an access to the memory word `address` made by the instruction at `instruction`
+    pub fn access(&mut self, address: u16, write: bool, instruction: usize) {
+        self.clock += 1;
+        let block = address as u32 / self.config.line_size;
+        let set_count = self.sets.len() as u32;
+        let tag = block / set_count;
+        let write_back = self.config.write_policy == WritePolicy::WriteBack;
+        if write && !write_back {
+            self.stats.memory_writes += 1;
+        }
+        let entry = self.stats.per_instruction.entry(instruction).or_insert((0, 0));
+        let set = &mut self.sets[(block % set_count) as usize];
+        if let Some(line) = set.iter_mut().find(|line| line.tag == tag) {
+            line.used = self.clock;
+            line.dirty |= write && write_back;
+            self.stats.hits += 1;
+            entry.0 += 1;
+            return;
+        }
+        self.stats.misses += 1;
+        entry.1 += 1;
+        if write && !write_back {
+            return;
+        }
+        let line = Line {
+            tag,
+            dirty: write,
+            loaded: self.clock,
+            used: self.clock,
+        };
+        if set.len() < self.config.associativity as usize {
+            set.push(line);
+            return;
+        }
+        let victim = match self.config.replacement {
+            Replacement::Lru => set.iter().enumerate().min_by_key(|(_, l)| l.used),
+            Replacement::Fifo => set.iter().enumerate().min_by_key(|(_, l)| l.loaded),
+        }
+        .map(|(i, _)| i)
+        .unwrap();
+        if set[victim].dirty {
+            self.stats.writebacks += 1;
+        }
+        set[victim] = line;
+    }
+
+    // hits, misses and miss rate of each instruction that accessed memory
+    pub fn report(&self, instructions: &[Instruction]) -> String {
+        let mut report = format!(
+            "{:>5}  {:<20}{:>10}{:>10}{:>10}\n",
+            "Index", "Instruction", "Hits", "Misses", "Miss rate"
+        );
+        for (index, (hits, misses)) in self.stats.per_instruction.iter() {
+            let rate = *misses as f64 / (hits + misses) as f64 * 100.0;
+            report.push_str(&format!(
+                "{:>5}  {:<20}{:>10}{:>10}{:>9.2}%\n",
+                index,
+                instructions[*index].to_string(),
+                hits,
+                misses,
+                rate
+            ));
+        }
+        report
+    }
+}
@@ -0,0 +1,191 @@
+use crate::enums::Instruction;
+use crate::program::{Program, RunOutcome};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    // a branch whose condition holds
+    Taken,
+    // the next instruction, after a branch whose condition does not hold
+    Fallthrough,
+    // a JMP, or a HLT going to the end of the program
+    Jump,
+}
+
+// instructions executed one after another, from `start` up to `end` excluded
+#[derive(Debug)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    // next blocks by index, None standing for the end of the program
+    pub successors: Vec<(Option<usize>, Edge)>,
+}
+
+// control-flow graph of a program, split in basic blocks at the labels and after
+// the branches, the jumps and HLT
+#[derive(Debug)]
+pub struct Cfg {
+    pub blocks: Vec<BasicBlock>,
+}
+
+// number of executions of each instruction during a run, and of the executions
+// that did not continue with the next instruction (taken branch, jump or HLT)
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub executions: Vec<u64>,
+    pub jumps: Vec<u64>,
+}
+
+// instructions that can be executed after the instruction at `index`,
+// `instructions.len()` standing for the end of the program
+pub fn successors(program: &Program, index: usize) -> Vec<usize> {
+    let instruction = &program.instructions[index];
+    let target = instruction.target().map(|l| program.find_label(&l));
+    match instruction {
+        Instruction::HLT(_) => vec![],
+        Instruction::JMP(_) => vec![target.unwrap()],
+        _ => match target {
+            Some(target) => vec![index + 1, target],
+            None => vec![index + 1],
+        },
+    }
+}
+
+impl Cfg {
+    pub fn new(program: &Program) -> Cfg {
+        let count = program.instructions.len();
+        let mut leaders: Vec<bool> = vec![false; count + 1];
+        leaders[0] = true;
+        for (index, instruction) in program.instructions.iter().enumerate() {
+            match instruction {
+                Instruction::LABEL(_) => leaders[index] = true,
+                Instruction::HLT(_) => leaders[index + 1] = true,
+                _ if instruction.is_branch() => leaders[index + 1] = true,
+                _ => {}
+            }
+        }
+        let starts: Vec<usize> = (0..count).filter(|i| leaders[*i]).collect();
+        let block_of = |index: usize| starts.iter().position(|s| *s == index);
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        for (i, start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).copied().unwrap_or(count);
+            let last = &program.instructions[end - 1];
+            let target = last
+                .target()
+                .map(|l| block_of(program.find_label(&l)).unwrap());
+            let successors = match last {
+                Instruction::HLT(_) => vec![(None, Edge::Jump)],
+                Instruction::JMP(_) => vec![(target, Edge::Jump)],
+                _ if last.is_branch() => {
+                    vec![(block_of(end), Edge::Fallthrough), (target, Edge::Taken)]
+                }
+                _ => vec![(block_of(end), Edge::Fallthrough)],
+            };
+            blocks.push(BasicBlock {
+                start: *start,
+                end,
+                successors,
+            });
+        }
+        Cfg { blocks }
+    }
+
+    // block containing the instruction at `index`
+    pub fn block_of(&self, index: usize) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|b| b.start <= index && index < b.end)
+    }
+
+    // number of times an edge leaving a block was followed during a run
+    fn edge_count(&self, profile: &Profile, block: usize, edge: Edge) -> u64 {
+        let last = self.blocks[block].end - 1;
+        match edge {
+            Edge::Fallthrough => profile.executions[last] - profile.jumps[last],
+            _ => profile.jumps[last],
+        }
+    }
+
+    // graph in the DOT language of Graphviz, annotated with the execution counts of a run
+    pub fn to_dot(&self, program: &Program, profile: Option<&Profile>) -> String {
+        // number of times each block was entered
+        let mut entries: Vec<u64> = vec![0; self.blocks.len()];
+        if let Some(profile) = profile {
+            if profile.executions.first().is_some_and(|c| *c > 0) {
+                entries[0] = 1;
+            }
+            for (i, block) in self.blocks.iter().enumerate() {
+                for (next, edge) in block.successors.iter() {
+                    if let Some(next) = next {
+                        entries[*next] += self.edge_count(profile, i, *edge);
+                    }
+                }
+            }
+        }
+        let mut dot = String::from("digraph program {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        for (i, block) in self.blocks.iter().enumerate() {
+            let mut label = format!("B{}", i);
+            if profile.is_some() {
+                label.push_str(&format!(" ({}x)", entries[i]));
+            }
+            label.push_str("\\l");
+            for index in block.start..block.end {
+                let text = program.instruction_text(index).replace('"', "\\\"");
+                label.push_str(&format!("{}\\l", text));
+            }
+            dot.push_str(&format!("    B{} [label=\"{}\"];\n", i, label));
+        }
+        dot.push_str("    end [shape=oval, label=\"end\"];\n");
+        for (i, block) in self.blocks.iter().enumerate() {
+            for (next, edge) in block.successors.iter() {
+                let name = match next {
+                    Some(next) => format!("B{}", next),
+                    None => "end".to_string(),
+                };
+                let mut label = match edge {
+                    Edge::Taken => "taken".to_string(),
+                    Edge::Fallthrough => "fallthrough".to_string(),
+                    Edge::Jump => String::new(),
+                };
+                if let Some(profile) = profile {
+                    let count = self.edge_count(profile, i, *edge);
+                    label = format!("{} {}", label, count).trim().to_string();
+                }
+                let style = match edge {
+                    Edge::Taken => ", color=\"darkgreen\"",
+                    _ => "",
+                };
+                dot.push_str(&format!(
+                    "    B{} -> {} [label=\"{}\"{}];\n",
+                    i, name, label, style
+                ));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+// run a program to its end, counting the executions and the jumps of each instruction
+pub fn profile(program: &mut Program) -> (Profile, RunOutcome) {
+    let count = program.instructions.len();
+    let mut profile = Profile {
+        executions: vec![0; count],
+        jumps: vec![0; count],
+    };
+    let outcome = loop {
+        if program.is_halted() {
+            break program.end_outcome();
+        }
+        match program.step() {
+            Ok(index) => {
+                profile.executions[index] += 1;
+                if program.counter() != index + 1 {
+                    profile.jumps[index] += 1;
+                }
+            }
+            Err(outcome) => break outcome,
+        }
+    };
+    (profile, outcome)
+}
@@ -0,0 +1,401 @@
+use crate::arch::Architecture;
+use crate::enums::{FloatRegister, Register};
+use crate::preprocess::Location;
+use crate::program::AssemblyError;
+use crate::pseudo::is_mnemonic;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Symbol(char),
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c.is_alphanumeric() {
+            let mut word = c.to_string();
+            while let Some(next) = chars.next_if(|c| c.is_alphanumeric()) {
+                word.push(next);
+            }
+            if c.is_ascii_digit() {
+                match word.parse::<i64>() {
+                    Ok(number) => tokens.push(Token::Number(number)),
+                    Err(_) => return Err(format!("{} is not a number", word)),
+                }
+            } else {
+                tokens.push(Token::Name(word));
+            }
+            continue;
+        }
+        if "+-*/%()".contains(c) {
+            tokens.push(Token::Symbol(c));
+            continue;
+        }
+        return Err(format!("unexpected character {}", c));
+    }
+    Ok(tokens)
+}
+
+// recursive descent over the tokens of an expression, `*`, `/` and `%` binding tighter
+// than `+` and `-`
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    value: &'a mut dyn FnMut(&str) -> Result<i64, String>,
+}
+
+impl Parser<'_> {
+    fn next_if(&mut self, symbols: &str) -> Option<char> {
+        match self.tokens.get(self.position) {
+            Some(Token::Symbol(c)) if symbols.contains(*c) => {
+                self.position += 1;
+                Some(*c)
+            }
+            _ => None,
+        }
+    }
+
+    fn sum(&mut self) -> Result<i64, String> {
+        let mut value = self.product()?;
+        while let Some(operator) = self.next_if("+-") {
+            let right = self.product()?;
+            value = match operator {
+                '+' => value.checked_add(right),
+                _ => value.checked_sub(right),
+            }
+            .ok_or("the value is too large")?;
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<i64, String> {
+        let mut value = self.factor()?;
+        while let Some(operator) = self.next_if("*/%") {
+            let right = self.factor()?;
+            if operator != '*' && right == 0 {
+                return Err("division by zero".to_string());
+            }
+            value = match operator {
+                '*' => value.checked_mul(right),
+                '/' => value.checked_div(right),
+                _ => value.checked_rem(right),
+            }
+            .ok_or("the value is too large")?;
+        }
+        Ok(value)
+    }
+
+    fn factor(&mut self) -> Result<i64, String> {
+        if self.next_if("-").is_some() {
+            return Ok(-self.factor()?);
+        }
+        if self.next_if("(").is_some() {
+            let value = self.sum()?;
+            if self.next_if(")").is_none() {
+                return Err("missing )".to_string());
+            }
+            return Ok(value);
+        }
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        match token {
+            Some(Token::Number(number)) => Ok(*number),
+            Some(Token::Name(name)) => {
+                let name = name.clone();
+                (self.value)(&name)
+            }
+            _ => Err("expected a number or a constant".to_string()),
+        }
+    }
+}
+
+// value of an expression made of numbers, constants, `+ - * / %` and parentheses,
+// `value` giving the value of each constant
+pub fn evaluate(
+    expression: &str,
+    value: &mut dyn FnMut(&str) -> Result<i64, String>,
+) -> Result<i64, String> {
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        position: 0,
+        value,
+    };
+    let result = parser.sum()?;
+    if parser.position < parser.tokens.len() {
+        return Err(format!("unexpected {:?}", parser.tokens[parser.position]));
+    }
+    Ok(result)
+}
+
+// constants defined by `.equ NAME value` or in a #CONST section, evaluated on demand
+// since a definition can use the constants defined after it
+struct Constants {
+    definitions: HashMap<String, (String, Location)>,
+    values: HashMap<String, i64>,
+    // constants whose definition is being evaluated
+    evaluating: Vec<String>,
+}
+
+impl Constants {
+    fn value(&mut self, name: &str) -> Result<i64, AssemblyError> {
+        if let Some(value) = self.values.get(name) {
+            return Ok(*value);
+        }
+        let (expression, location) = self.definitions[name].clone();
+        if self.evaluating.iter().any(|n| n == name) {
+            let message = format!("constant {} is defined from itself", name);
+            return Err(AssemblyError::at(&location, message));
+        }
+        self.evaluating.push(name.to_string());
+        // the error of a constant used by this one is reported at its own definition
+        let mut failure: Option<AssemblyError> = None;
+        let result = evaluate(&expression, &mut |word| {
+            if !self.definitions.contains_key(word) {
+                return Err(format!("unknown constant {}", word));
+            }
+            self.value(word).map_err(|e| {
+                failure = Some(e);
+                String::new()
+            })
+        });
+        self.evaluating.pop();
+        if let Some(failure) = failure {
+            return Err(failure);
+        }
+        let value = result.map_err(|e| {
+            AssemblyError::at(
+                &location,
+                format!("invalid expression {}: {}", expression, e),
+            )
+        })?;
+        self.values.insert(name.to_string(), value);
+        Ok(value)
+    }
+}
+
+// value of an expression used in the program, which must fit in the `bits` of a constant,
+// of an offset or of a #DATA value in the encoding of the machine
+fn checked(
+    expression: &str,
+    values: &HashMap<String, i64>,
+    what: &str,
+    bits: u32,
+) -> Result<i64, String> {
+    let value = evaluate(expression, &mut |word| {
+        values
+            .get(word)
+            .copied()
+            .ok_or(format!("unknown constant {}", word))
+    })
+    .map_err(|e| format!("invalid expression {}: {}", expression, e))?;
+    if !(0..1 << bits).contains(&value) {
+        if expression.parse::<i64>().is_ok() {
+            return Err(format!(
+                "{} does not fit in the {} bits of {}",
+                value, bits, what
+            ));
+        }
+        return Err(format!(
+            "{} is {}, which does not fit in the {} bits of {}",
+            expression, value, bits, what
+        ));
+    }
+    Ok(value)
+}
+
+fn is_name(word: &str) -> bool {
+    word.starts_with(|c: char| c.is_alphabetic()) && word.chars().all(|c| c.is_alphanumeric())
+}
+
+// operand of an instruction with its expressions replaced by their value, the names that
+// are not constants being kept as written and the numbers checked against the encoding
+fn operand(word: &str, values: &HashMap<String, i64>, bits: u32) -> Result<String, String> {
+    // an offset is written `A[i]` or `A+i`, where `A` is a variable
+    let offset = |expression: &str| match expression.parse::<u16>() {
+        Ok(_) => Ok(expression.to_string()),
+        Err(_) => checked(expression, values, "an offset", bits).map(|v| v.to_string()),
+    };
+    if let Some((base, index)) = word.strip_suffix(']').and_then(|w| w.split_once('[')) {
+        return Ok(format!("{}[{}]", base, offset(index)?));
+    }
+    if is_name(word) && !values.contains_key(word) {
+        return Ok(word.to_string());
+    }
+    if let Some((base, expression)) = word.split_once('+') {
+        if is_name(base) && !values.contains_key(base) {
+            return Ok(format!("{}+{}", base, offset(expression)?));
+        }
+    }
+    checked(word, values, "a constant", bits).map(|v| v.to_string())
+}
+
+// #DATA declaration `NAME value` or `NAME[length] value` with its expressions replaced
+fn declaration(text: &str, values: &HashMap<String, i64>, bits: u32) -> Result<String, String> {
+    let line = text.trim();
+    let (name, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let value = value.trim();
+    let name = match name.strip_suffix(']').and_then(|n| n.split_once('[')) {
+        Some((name, length)) if length.parse::<u16>().is_err() => {
+            let length = checked(length, values, "an array length", bits)?;
+            format!("{}[{}]", name, length)
+        }
+        _ => name.to_string(),
+    };
+    // strings and numbers are kept as written, a floating-point number being rejected
+    // when the declaration is assembled
+    let float = value.contains('.') && value.parse::<f32>().is_ok();
+    let value = if value.starts_with('"') || value.parse::<i64>().is_ok() || float {
+        value.to_string()
+    } else {
+        checked(value, values, "a value", bits)?.to_string()
+    };
+    Ok(format!("{} {}", name, value))
+}
+
+// name and expression of the definition of a constant, with the names already in use
+fn definition(
+    text: &str,
+    location: &Location,
+    used: &HashSet<String>,
+    definitions: &HashMap<String, (String, Location)>,
+) -> Result<(String, (String, Location)), AssemblyError> {
+    let error = |message: String| Err(AssemblyError::at(location, message));
+    let mut words = text.split(';').next().unwrap().split_whitespace();
+    let name = match words.next() {
+        Some(name) => name,
+        None => return error("expected a name and a value".to_string()),
+    };
+    let expression = words.collect::<Vec<&str>>().join(" ");
+    if expression.is_empty() {
+        return error(format!("constant {} has no value", name));
+    }
+    if !is_name(name) {
+        return error(format!(
+            "constant name {} must start with a letter and hold only letters and digits",
+            name
+        ));
+    }
+    if Register::from_str(name).is_ok() || FloatRegister::from_str(name).is_ok() {
+        return error(format!("{} is a register and cannot be a constant", name));
+    }
+    if is_mnemonic(name) {
+        return error(format!(
+            "{} is an instruction and cannot be a constant",
+            name
+        ));
+    }
+    if used.contains(name) {
+        return error(format!(
+            "{} is a label or a variable and cannot be a constant",
+            name
+        ));
+    }
+    if definitions.contains_key(name) {
+        return error(format!("constant {} is already defined", name));
+    }
+    Ok((name.to_string(), (expression, location.clone())))
+}
+
+// remove the definitions of constants, `lines` of #CONST sections and `.equ` lines
+// anywhere, and replace the expressions of the operands and #DATA values by their value.
+// The numbers written as operands are range checked even without constants, for the
+// machine of the #CONFIG line or `architecture` without one.
+pub fn resolve_constants(
+    lines: Vec<(String, Location)>,
+    sections: Vec<(String, Location)>,
+    architecture: Architecture,
+) -> Result<Vec<(String, Location)>, AssemblyError> {
+    // names of the labels and of the variables, which cannot be constants
+    let mut used: HashSet<String> = HashSet::new();
+    let mut data = false;
+    let mut architecture = architecture;
+    for (text, _) in lines.iter() {
+        // an invalid #CONFIG is reported when the program is assembled
+        if let Some(Ok(config)) = Architecture::from_directive(text) {
+            architecture = config;
+        }
+        if text.starts_with('#') {
+            data = text.starts_with("#DATA");
+            continue;
+        }
+        let code = text.split(';').next().unwrap().trim();
+        let name = match code.strip_suffix(':') {
+            Some(label) => label,
+            None if data => code.split(['[', ' ', '\t']).next().unwrap(),
+            None => continue,
+        };
+        used.insert(name.to_string());
+    }
+
+    let mut definitions: HashMap<String, (String, Location)> = HashMap::new();
+    let mut program: Vec<(String, Location)> = Vec::new();
+    for (text, location) in sections {
+        let code = text.split(';').next().unwrap().trim();
+        if !code.is_empty() {
+            let (name, definition) = definition(code, &location, &used, &definitions)?;
+            definitions.insert(name, definition);
+        }
+    }
+    for (text, location) in lines {
+        match text.trim().strip_prefix(".equ") {
+            Some(rest) if rest.is_empty() || rest.starts_with(char::is_whitespace) => {
+                let (name, definition) = definition(rest, &location, &used, &definitions)?;
+                definitions.insert(name, definition);
+            }
+            _ => program.push((text, location)),
+        }
+    }
+    let mut constants = Constants {
+        definitions,
+        values: HashMap::new(),
+        evaluating: Vec::new(),
+    };
+    let mut names: Vec<String> = constants.definitions.keys().cloned().collect();
+    // the first error is the one of the first definition in the source
+    names.sort_by_key(|n| {
+        let location = &constants.definitions[n].1;
+        (location.file.clone(), location.line)
+    });
+    for name in names {
+        constants.value(&name)?;
+    }
+    let values = constants.values;
+    let bits = architecture.value_bits();
+
+    let mut resolved: Vec<(String, Location)> = Vec::with_capacity(program.len());
+    let mut data = false;
+    for (text, location) in program {
+        let code = text.split(';').next().unwrap().trim();
+        if text.starts_with('#') {
+            data = text.starts_with("#DATA");
+        }
+        if text.starts_with('#') || code.is_empty() || code.ends_with(':') {
+            resolved.push((text, location));
+            continue;
+        }
+        let line = if data {
+            declaration(&text, &values, bits)
+        } else {
+            let mut words = code.split_whitespace();
+            let mnemonic = words.next().unwrap().to_string();
+            words
+                .map(|word| operand(word, &values, bits))
+                .collect::<Result<Vec<String>, String>>()
+                .map(|operands| {
+                    let line = format!("    {} {}", mnemonic, operands.join(" "));
+                    line.trim_end().to_string()
+                })
+        };
+        let line = line.map_err(|e| AssemblyError::at(&location, e))?;
+        resolved.push((line, location));
+    }
+    Ok(resolved)
+}
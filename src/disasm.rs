@@ -0,0 +1,206 @@
+use crate::enums::{AddressNames, FloatParameter, Instruction, Parameter, Variable};
+use crate::file::{LineCategory, LineType};
+use crate::preprocess::Location;
+use crate::program::AssemblyError;
+use std::collections::BTreeMap;
+
+// translate the lines of a program, text or binary, back to the source of the program.
+// The names of a binary program are lost, the variables are named after their address
+// (`V0`, `V1`, ...) and the labels after their number (`L0`, `L1`, ...).
+pub fn disassemble(buffer: Vec<LineType>) -> Result<String, AssemblyError> {
+    let mut variable_names = AddressNames::new();
+    let mut label_names = AddressNames::new();
+    let mut data: Vec<Instruction> = Vec::new();
+    let mut code: Vec<Instruction> = Vec::new();
+    let mut current_category: LineCategory = LineCategory::NONE;
+    // #CONFIG line of a program for another machine than the classic one
+    let mut config: Option<String> = None;
+    for (number, line) in buffer.into_iter().enumerate() {
+        let error = |message: String| AssemblyError::at(&Location::new(number + 1), message);
+        if let Some(architecture) = line.config() {
+            match architecture {
+                Ok(architecture) => config = Some(format!("#CONFIG {}\n\n", architecture)),
+                Err(e) => return Err(error(format!("Invalid #CONFIG: {}", e))),
+            }
+            continue;
+        }
+        if line.is_category() {
+            current_category = line.get_category().map_err(error)?;
+            continue;
+        }
+        if line.is_empty() || line.is_comment() {
+            continue;
+        }
+        if current_category == LineCategory::NONE {
+            return Err(error("Invalid category".to_string()));
+        }
+        let instructions = line
+            .translate_all(&current_category, &mut variable_names, &mut label_names)
+            .map_err(error)?;
+        match current_category {
+            LineCategory::DATA => data.extend(instructions),
+            _ => code.extend(instructions),
+        }
+    }
+
+    // arrays by address of their first cell: (length, value of each cell)
+    let mut arrays: BTreeMap<u16, (u16, Vec<u64>)> = BTreeMap::new();
+    // cells of an array set one by one, which only happens for strings
+    let mut strings: Vec<u16> = Vec::new();
+    for instruction in data.iter() {
+        match instruction {
+            Instruction::ARRAY(variable, value, length) => {
+                arrays.insert(variable.name, (*length, vec![*value; *length as usize]));
+            }
+            Instruction::VARIABLE(variable, value) => {
+                if let Some((start, (_, cells))) = array_of(&mut arrays, variable.name) {
+                    cells[(variable.name - start) as usize] = *value;
+                    if !strings.contains(&start) {
+                        strings.push(start);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut source = config.unwrap_or_default();
+    source.push_str("#DATA\n");
+    for instruction in data.iter() {
+        match instruction {
+            Instruction::ARRAY(variable, _, _) if strings.contains(&variable.name) => {
+                let (_, cells) = &arrays[&variable.name];
+                source.push_str(&format!("{} \"{}\"\n", name(variable), escape(cells)));
+            }
+            Instruction::ARRAY(variable, value, length) => {
+                source.push_str(&format!("{}[{}] {}\n", name(variable), length, value));
+            }
+            Instruction::VARIABLE(variable, value)
+                if array_of(&mut arrays, variable.name).is_none() =>
+            {
+                source.push_str(&format!("{} {}\n", name(variable), value));
+            }
+            _ => {}
+        }
+    }
+    source.push_str("\n#CODE\n");
+    for instruction in code.iter() {
+        let line = match instruction {
+            Instruction::LABEL(_) => instruction.to_string(),
+            _ => rename(instruction, &arrays),
+        };
+        source.push_str(&line);
+        source.push('\n');
+    }
+    Ok(source)
+}
+
+// array containing an address, by the address of its first cell
+fn array_of(
+    arrays: &mut BTreeMap<u16, (u16, Vec<u64>)>,
+    address: u16,
+) -> Option<(u16, &mut (u16, Vec<u64>))> {
+    let (start, array) = arrays.range_mut(..=address).next_back()?;
+    if address < start + array.0 {
+        return Some((*start, array));
+    }
+    None
+}
+
+fn name(variable: &Variable) -> String {
+    match variable.alias {
+        Some(alias) => alias.to_string(),
+        None => format!("V{}", variable.name),
+    }
+}
+
+// name of a variable of a binary program, a cell of an array is named after the array
+fn address_name(variable: &Variable, arrays: &BTreeMap<u16, (u16, Vec<u64>)>) -> String {
+    if variable.alias.is_some() {
+        return name(variable);
+    }
+    match arrays.range(..=variable.name).next_back() {
+        Some((start, (length, _))) if variable.name < start + length && variable.name > *start => {
+            format!("V{}[{}]", start, variable.name - start)
+        }
+        _ => name(variable),
+    }
+}
+
+// text of an instruction with the variables named after the arrays they belong to
+fn rename(instruction: &Instruction, arrays: &BTreeMap<u16, (u16, Vec<u64>)>) -> String {
+    let text = instruction.to_string();
+    let mut words: Vec<String> = text.split(' ').map(|w| w.to_string()).collect();
+    let mut operands: Vec<Option<&Variable>> = vec![None];
+    match instruction {
+        Instruction::STR(v, p) => {
+            operands.push(Some(v));
+            operands.push(variable_of(p));
+        }
+        Instruction::PRINTS(v) => operands.push(Some(v)),
+        Instruction::BEQ(p1, p2, _)
+        | Instruction::BNE(p1, p2, _)
+        | Instruction::BBG(p1, p2, _)
+        | Instruction::BSM(p1, p2, _) => {
+            operands.push(variable_of(p1));
+            operands.push(variable_of(p2));
+        }
+        Instruction::LDA(_, p)
+        | Instruction::AND(_, p)
+        | Instruction::OR(_, p)
+        | Instruction::ADD(_, p)
+        | Instruction::SUB(_, p)
+        | Instruction::DIV(_, p)
+        | Instruction::MUL(_, p)
+        | Instruction::MOD(_, p) => {
+            operands.push(None);
+            operands.push(variable_of(p));
+        }
+        Instruction::PUSH(p)
+        | Instruction::IN(p)
+        | Instruction::OUT(p, _)
+        | Instruction::GETC(p)
+        | Instruction::HLT(Some(p)) => operands.push(variable_of(p)),
+        Instruction::FSTR(v, _) => operands.push(Some(v)),
+        Instruction::ITOF(_, p) => {
+            operands.push(None);
+            operands.push(variable_of(p));
+        }
+        Instruction::FOUT(FloatParameter::Variable(v)) => operands.push(Some(v)),
+        _ => {
+            if let Some(FloatParameter::Variable(v)) = instruction.float_parameter() {
+                operands.push(None);
+                operands.push(Some(v));
+            }
+        }
+    }
+    for (word, operand) in words.iter_mut().zip(operands) {
+        if let Some(variable) = operand {
+            *word = address_name(variable, arrays);
+        }
+    }
+    words.join(" ")
+}
+
+fn variable_of(parameter: &Parameter) -> Option<&Variable> {
+    match parameter {
+        Parameter::Variable(v) => Some(v),
+        _ => None,
+    }
+}
+
+// characters of a zero-terminated string, with the escapes accepted in #DATA
+fn escape(cells: &[u64]) -> String {
+    let mut text = String::new();
+    for cell in cells.iter().take_while(|c| **c != 0) {
+        match u32::try_from(*cell).ok().and_then(char::from_u32) {
+            Some('\n') => text.push_str("\\n"),
+            Some('\t') => text.push_str("\\t"),
+            Some('"') => text.push_str("\\\""),
+            Some('\\') => text.push_str("\\\\"),
+            Some(c) => text.push(c),
+            None => text.push('?'),
+        }
+    }
+    text
+}
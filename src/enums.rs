@@ -2,12 +2,20 @@
 use core::fmt;
 use regex::Regex;
 use std::{fmt::Debug, hash::Hash, str::FromStr};
-pub type u2 = u8; // Register size (4 possible registers)
+pub type u2 = u8; // Register size (4 possible registers, 16 with the wide encoding)
 pub type u3 = u8; // Label name size (8 possible labels per program)
 pub type u12 = u32; // Parameter type + value size (4096 possible parameters per program)
 pub type u10 = u16; // Variable name size (1024 possible variables per program)
 pub type Constant = u10; // Constant size (1024 possible constants per program)
 
+// names of the instructions in the #CODE section, PUTC being OUT in character mode
+pub const MNEMONICS: [&str; 40] = [
+    "LDA", "STR", "PUSH", "POP", "AND", "OR", "NOT", "ADD", "SUB", "DIV", "MUL", "MOD", "INC",
+    "DEC", "BEQ", "BNE", "BBG", "BSM", "JMP", "SRL", "SRR", "HLT", "IN", "OUT", "GETC", "PUTC",
+    "PRINTS", "FLDA", "FSTR", "FADD", "FSUB", "FMUL", "FDIV", "FBEQ", "FBNE", "FBSM", "FBBG",
+    "ITOF", "FTOI", "FOUT",
+];
+
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub enum Instruction {
     LDA(Register, Parameter),
@@ -31,12 +39,317 @@ pub enum Instruction {
     JMP(Label),
     SRL(Register, Constant),
     SRR(Register, Constant),
-    HLT,
-    VARIABLE(Variable, u32),
-    ARRAY(Variable, u32, u16),
+    HLT(Option<Parameter>),
+    VARIABLE(Variable, u64),
+    ARRAY(Variable, u64, u16),
     LABEL(Label),
     IN(Parameter),
-    OUT(Parameter),
+    OUT(Parameter, OutputFormat),
+    GETC(Parameter),
+    PRINTS(Variable),
+    FLDA(FloatRegister, FloatParameter),
+    FSTR(Variable, FloatRegister),
+    FADD(FloatRegister, FloatParameter),
+    FSUB(FloatRegister, FloatParameter),
+    FMUL(FloatRegister, FloatParameter),
+    FDIV(FloatRegister, FloatParameter),
+    FBEQ(FloatRegister, FloatParameter, Label),
+    FBNE(FloatRegister, FloatParameter, Label),
+    FBSM(FloatRegister, FloatParameter, Label),
+    FBBG(FloatRegister, FloatParameter, Label),
+    ITOF(FloatRegister, Parameter),
+    FTOI(Register, FloatRegister),
+    FOUT(FloatParameter),
+}
+
+impl Instruction {
+    // name of the instruction as written in the source
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::LDA(_, _) => "LDA",
+            Instruction::STR(_, _) => "STR",
+            Instruction::PUSH(_) => "PUSH",
+            Instruction::POP(_) => "POP",
+            Instruction::AND(_, _) => "AND",
+            Instruction::OR(_, _) => "OR",
+            Instruction::NOT(_) => "NOT",
+            Instruction::ADD(_, _) => "ADD",
+            Instruction::SUB(_, _) => "SUB",
+            Instruction::DIV(_, _) => "DIV",
+            Instruction::MUL(_, _) => "MUL",
+            Instruction::MOD(_, _) => "MOD",
+            Instruction::INC(_) => "INC",
+            Instruction::DEC(_) => "DEC",
+            Instruction::BEQ(_, _, _) => "BEQ",
+            Instruction::BNE(_, _, _) => "BNE",
+            Instruction::BBG(_, _, _) => "BBG",
+            Instruction::BSM(_, _, _) => "BSM",
+            Instruction::JMP(_) => "JMP",
+            Instruction::SRL(_, _) => "SRL",
+            Instruction::SRR(_, _) => "SRR",
+            Instruction::HLT(_) => "HLT",
+            Instruction::VARIABLE(_, _) => "VARIABLE",
+            Instruction::ARRAY(_, _, _) => "ARRAY",
+            Instruction::LABEL(_) => "LABEL",
+            Instruction::IN(_) => "IN",
+            Instruction::OUT(_, _) => "OUT",
+            Instruction::GETC(_) => "GETC",
+            Instruction::PRINTS(_) => "PRINTS",
+            Instruction::FLDA(_, _) => "FLDA",
+            Instruction::FSTR(_, _) => "FSTR",
+            Instruction::FADD(_, _) => "FADD",
+            Instruction::FSUB(_, _) => "FSUB",
+            Instruction::FMUL(_, _) => "FMUL",
+            Instruction::FDIV(_, _) => "FDIV",
+            Instruction::FBEQ(_, _, _) => "FBEQ",
+            Instruction::FBNE(_, _, _) => "FBNE",
+            Instruction::FBSM(_, _, _) => "FBSM",
+            Instruction::FBBG(_, _, _) => "FBBG",
+            Instruction::ITOF(_, _) => "ITOF",
+            Instruction::FTOI(_, _) => "FTOI",
+            Instruction::FOUT(_) => "FOUT",
+        }
+    }
+
+    // number of operands that read or write a memory address
+    pub fn memory_operands(&self) -> u32 {
+        let is_memory = |p: &Parameter| matches!(p, Parameter::Variable(_)) as u32;
+        match self {
+            Instruction::STR(_, p) => 1 + is_memory(p),
+            Instruction::LDA(_, p)
+            | Instruction::AND(_, p)
+            | Instruction::OR(_, p)
+            | Instruction::ADD(_, p)
+            | Instruction::SUB(_, p)
+            | Instruction::DIV(_, p)
+            | Instruction::MUL(_, p)
+            | Instruction::MOD(_, p) => is_memory(p),
+            Instruction::PUSH(p)
+            | Instruction::IN(p)
+            | Instruction::OUT(p, _)
+            | Instruction::GETC(p) => is_memory(p),
+            Instruction::PRINTS(_) => 1,
+            Instruction::HLT(Some(p)) => is_memory(p),
+            Instruction::BEQ(p1, p2, _)
+            | Instruction::BNE(p1, p2, _)
+            | Instruction::BBG(p1, p2, _)
+            | Instruction::BSM(p1, p2, _) => is_memory(p1) + is_memory(p2),
+            Instruction::ITOF(_, p) => is_memory(p),
+            Instruction::FSTR(_, _) => 1,
+            _ => match self.float_parameter() {
+                Some(FloatParameter::Variable(_)) => 1,
+                _ => 0,
+            },
+        }
+    }
+
+    pub fn is_branch(&self) -> bool {
+        matches!(
+            self,
+            Instruction::BEQ(_, _, _)
+                | Instruction::BNE(_, _, _)
+                | Instruction::BBG(_, _, _)
+                | Instruction::BSM(_, _, _)
+                | Instruction::FBEQ(_, _, _)
+                | Instruction::FBNE(_, _, _)
+                | Instruction::FBSM(_, _, _)
+                | Instruction::FBBG(_, _, _)
+                | Instruction::JMP(_)
+        )
+    }
+
+    // registers whose value is read by the instruction
+    pub fn registers_read(&self) -> Vec<Register> {
+        let (mut registers, parameters): (Vec<Register>, Vec<&Parameter>) = match self {
+            Instruction::LDA(_, p) | Instruction::STR(_, p) | Instruction::ITOF(_, p) => {
+                (vec![], vec![p])
+            }
+            Instruction::PUSH(p) | Instruction::OUT(p, _) | Instruction::HLT(Some(p)) => {
+                (vec![], vec![p])
+            }
+            Instruction::AND(r, p)
+            | Instruction::OR(r, p)
+            | Instruction::ADD(r, p)
+            | Instruction::SUB(r, p)
+            | Instruction::DIV(r, p)
+            | Instruction::MUL(r, p)
+            | Instruction::MOD(r, p) => (vec![*r], vec![p]),
+            Instruction::BEQ(p1, p2, _)
+            | Instruction::BNE(p1, p2, _)
+            | Instruction::BBG(p1, p2, _)
+            | Instruction::BSM(p1, p2, _) => (vec![], vec![p1, p2]),
+            Instruction::NOT(r)
+            | Instruction::INC(r)
+            | Instruction::DEC(r)
+            | Instruction::SRL(r, _)
+            | Instruction::SRR(r, _) => (vec![*r], vec![]),
+            _ => (vec![], vec![]),
+        };
+        for parameter in parameters {
+            if let Parameter::Register(r) = parameter {
+                registers.push(*r);
+            }
+        }
+        registers
+    }
+
+    // register whose value is written by the instruction
+    pub fn register_written(&self) -> Option<Register> {
+        match self {
+            Instruction::LDA(r, _)
+            | Instruction::POP(r)
+            | Instruction::AND(r, _)
+            | Instruction::OR(r, _)
+            | Instruction::NOT(r)
+            | Instruction::ADD(r, _)
+            | Instruction::SUB(r, _)
+            | Instruction::DIV(r, _)
+            | Instruction::MUL(r, _)
+            | Instruction::MOD(r, _)
+            | Instruction::INC(r)
+            | Instruction::DEC(r)
+            | Instruction::SRL(r, _)
+            | Instruction::SRR(r, _)
+            | Instruction::IN(Parameter::Register(r))
+            | Instruction::GETC(Parameter::Register(r))
+            | Instruction::FTOI(r, _) => Some(*r),
+            _ => None,
+        }
+    }
+
+    // parameters of the instruction, in the order of the source
+    pub fn parameters(&self) -> Vec<&Parameter> {
+        match self {
+            Instruction::LDA(_, p)
+            | Instruction::STR(_, p)
+            | Instruction::AND(_, p)
+            | Instruction::OR(_, p)
+            | Instruction::ADD(_, p)
+            | Instruction::SUB(_, p)
+            | Instruction::DIV(_, p)
+            | Instruction::MUL(_, p)
+            | Instruction::MOD(_, p)
+            | Instruction::PUSH(p)
+            | Instruction::IN(p)
+            | Instruction::OUT(p, _)
+            | Instruction::GETC(p)
+            | Instruction::ITOF(_, p)
+            | Instruction::HLT(Some(p)) => vec![p],
+            Instruction::BEQ(p1, p2, _)
+            | Instruction::BNE(p1, p2, _)
+            | Instruction::BBG(p1, p2, _)
+            | Instruction::BSM(p1, p2, _) => vec![p1, p2],
+            _ => vec![],
+        }
+    }
+
+    // memory addresses read or written by the instruction
+    pub fn variables(&self) -> Vec<Variable> {
+        let mut variables: Vec<Variable> = match self {
+            Instruction::STR(v, _) | Instruction::PRINTS(v) | Instruction::FSTR(v, _) => vec![*v],
+            _ => match self.float_parameter() {
+                Some(FloatParameter::Variable(v)) => vec![*v],
+                _ => vec![],
+            },
+        };
+        for parameter in self.parameters() {
+            if let Parameter::Variable(v) = parameter {
+                variables.push(*v);
+            }
+        }
+        variables
+    }
+
+    // label the instruction can jump to
+    pub fn target(&self) -> Option<Label> {
+        match self {
+            Instruction::BEQ(_, _, l)
+            | Instruction::BNE(_, _, l)
+            | Instruction::BBG(_, _, l)
+            | Instruction::BSM(_, _, l)
+            | Instruction::FBEQ(_, _, l)
+            | Instruction::FBNE(_, _, l)
+            | Instruction::FBSM(_, _, l)
+            | Instruction::FBBG(_, _, l)
+            | Instruction::JMP(l) => Some(*l),
+            _ => None,
+        }
+    }
+
+    // floating-point operand of an instruction of the FPU
+    pub fn float_parameter(&self) -> Option<&FloatParameter> {
+        match self {
+            Instruction::FLDA(_, p)
+            | Instruction::FADD(_, p)
+            | Instruction::FSUB(_, p)
+            | Instruction::FMUL(_, p)
+            | Instruction::FDIV(_, p)
+            | Instruction::FBEQ(_, p, _)
+            | Instruction::FBNE(_, p, _)
+            | Instruction::FBSM(_, p, _)
+            | Instruction::FBBG(_, p, _)
+            | Instruction::FOUT(p) => Some(p),
+            _ => None,
+        }
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(
+            self,
+            Instruction::FSTR(_, _) | Instruction::ITOF(_, _) | Instruction::FTOI(_, _)
+        ) || self.float_parameter().is_some()
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::LDA(r, p)
+            | Instruction::AND(r, p)
+            | Instruction::OR(r, p)
+            | Instruction::ADD(r, p)
+            | Instruction::SUB(r, p)
+            | Instruction::DIV(r, p)
+            | Instruction::MUL(r, p)
+            | Instruction::MOD(r, p) => write!(f, "{} {} {}", self.mnemonic(), r, p),
+            Instruction::STR(v, p) => write!(f, "STR {:?} {}", v, p),
+            Instruction::PUSH(p)
+            | Instruction::IN(p)
+            | Instruction::OUT(p, OutputFormat::Decimal)
+            | Instruction::GETC(p) => write!(f, "{} {}", self.mnemonic(), p),
+            Instruction::OUT(p, format) => write!(f, "OUT {} {}", p, format),
+            Instruction::PRINTS(v) => write!(f, "PRINTS {:?}", v),
+            Instruction::POP(r) | Instruction::NOT(r) | Instruction::INC(r) | Instruction::DEC(r) => {
+                write!(f, "{} {}", self.mnemonic(), r)
+            }
+            Instruction::BEQ(p1, p2, l)
+            | Instruction::BNE(p1, p2, l)
+            | Instruction::BBG(p1, p2, l)
+            | Instruction::BSM(p1, p2, l) => write!(f, "{} {} {} {}", self.mnemonic(), p1, p2, l),
+            Instruction::JMP(l) => write!(f, "JMP {}", l),
+            Instruction::SRL(r, c) | Instruction::SRR(r, c) => {
+                write!(f, "{} {} {}", self.mnemonic(), r, c)
+            }
+            Instruction::HLT(None) => write!(f, "HLT"),
+            Instruction::HLT(Some(p)) => write!(f, "HLT {}", p),
+            Instruction::VARIABLE(v, i) => write!(f, "{:?} {}", v, i),
+            Instruction::ARRAY(v, i, l) => write!(f, "{:?}[{}] {}", v, l, i),
+            Instruction::LABEL(l) => write!(f, "{}:", l),
+            Instruction::FLDA(r, p)
+            | Instruction::FADD(r, p)
+            | Instruction::FSUB(r, p)
+            | Instruction::FMUL(r, p)
+            | Instruction::FDIV(r, p) => write!(f, "{} {} {}", self.mnemonic(), r, p),
+            Instruction::FSTR(v, r) => write!(f, "FSTR {:?} {}", v, r),
+            Instruction::FBEQ(r, p, l)
+            | Instruction::FBNE(r, p, l)
+            | Instruction::FBSM(r, p, l)
+            | Instruction::FBBG(r, p, l) => write!(f, "{} {} {} {}", self.mnemonic(), r, p, l),
+            Instruction::ITOF(r, p) => write!(f, "ITOF {} {}", r, p),
+            Instruction::FTOI(r, s) => write!(f, "FTOI {} {}", r, s),
+            Instruction::FOUT(p) => write!(f, "FOUT {}", p),
+        }
+    }
 }
 
 impl Into<u32> for Instruction {
@@ -223,10 +536,18 @@ impl Into<u32> for Instruction {
                 res = res << 15; // 18 bits to get to 32 bits
                 return res;
             }
-            Instruction::HLT => {
+            Instruction::HLT(p) => {
                 let mut res: u32 = 0b10101;
-                res = res << 27; // 27 bits to get to 32 bits
-                return res;
+                res <<= 1;
+                if let Some(p) = p {
+                    res |= 0b1; // 1 bit set when there is an exit code
+                    res <<= 12;
+                    res |= Into::<u12>::into(p); // 12 bits for the parameter
+                } else {
+                    res <<= 12;
+                }
+                res <<= 14; // 14 bits to get to 32 bits
+                res
             }
             Instruction::VARIABLE(v, i) => {
                 // first bit is data type
@@ -235,8 +556,8 @@ impl Into<u32> for Instruction {
                 res = res << 10;
                 res = res | Into::<u16>::into(v) as u32;
                 // next 10 bits are the value
-                res = res << 10;
-                res = res | (i & 0b1111111111);
+                res <<= 10;
+                res |= i as u32 & 0b1111111111;
                 // shift left 11 bits to get to 32 bits
                 res = res << 11;
                 return res;
@@ -251,8 +572,8 @@ impl Into<u32> for Instruction {
                 res = res << 10;
                 res = res | (l as u32 & 0b1111111111);
                 // next 10 bits are the value
-                res = res << 10;
-                res = res | (i & 0b1111111111);
+                res <<= 10;
+                res |= i as u32 & 0b1111111111;
                 // shift left 1 bits to get to 32 bits
                 res = res << 1;
                 return res;
@@ -271,13 +592,203 @@ impl Into<u32> for Instruction {
                 res = res << 15; // 15 bits to get to 32 bits
                 return res;
             }
-            Instruction::OUT(p) => {
-                let mut res = 0b10111 as u32; // 5 bits for the instruction
-                res = res << 12;
-                res = res | Into::<u12>::into(p); // 12 bits for the parameter
-                res = res << 15; // 15 bits to get to 32 bits
-                return res;
+            Instruction::OUT(p, format) => {
+                let mut res = 0b10111_u32; // 5 bits for the instruction
+                res <<= 12;
+                res |= Into::<u12>::into(p); // 12 bits for the parameter
+                res <<= 3;
+                res |= Into::<u32>::into(format); // 3 bits for the output mode
+                res <<= 12; // 12 bits to get to 32 bits
+                res
+            }
+            Instruction::GETC(p) => {
+                let mut res = 0b10110_u32; // same instruction as IN
+                res <<= 12;
+                res |= Into::<u12>::into(p); // 12 bits for the parameter
+                res <<= 3;
+                res |= 0b001; // 3 bits for the character mode
+                res <<= 12; // 12 bits to get to 32 bits
+                res
+            }
+            Instruction::PRINTS(v) => {
+                let mut res = 0b10111_u32; // same instruction as OUT
+                res <<= 12;
+                res |= Into::<u12>::into(Parameter::Variable(v)); // 12 bits for the variable
+                res <<= 3;
+                res |= 0b010; // 3 bits for the string mode
+                res <<= 12; // 12 bits to get to 32 bits
+                res
             }
+            Instruction::FLDA(r, p) => {
+                let mut res: u32 = 0b11000;
+                res <<= 2;
+                res |= r as u32; // 2 bits for the floating-point register
+                res <<= 12;
+                res |= Into::<u12>::into(p); // 12 bits for the parameter
+                res <<= 13; // 13 bits to get to 32 bits
+                res
+            }
+            Instruction::FSTR(v, r) => {
+                let mut res: u32 = 0b11001;
+                res <<= 10;
+                res |= Into::<u10>::into(v) as u32; // 10 bits for the variable
+                res <<= 2;
+                res |= r as u32; // 2 bits for the floating-point register
+                res <<= 15; // 15 bits to get to 32 bits
+                res
+            }
+            Instruction::FADD(r, p)
+            | Instruction::FSUB(r, p)
+            | Instruction::FMUL(r, p)
+            | Instruction::FDIV(r, p) => {
+                let operation = match self {
+                    Instruction::FADD(_, _) => 0b00,
+                    Instruction::FSUB(_, _) => 0b01,
+                    Instruction::FMUL(_, _) => 0b10,
+                    _ => 0b11,
+                };
+                let mut res: u32 = 0b11010;
+                res <<= 2;
+                res |= operation; // 2 bits for the operation
+                res <<= 2;
+                res |= r as u32; // 2 bits for the floating-point register
+                res <<= 12;
+                res |= Into::<u12>::into(p); // 12 bits for the parameter
+                res <<= 11; // 11 bits to get to 32 bits
+                res
+            }
+            Instruction::FBEQ(r, p, lbl)
+            | Instruction::FBNE(r, p, lbl)
+            | Instruction::FBSM(r, p, lbl)
+            | Instruction::FBBG(r, p, lbl) => {
+                let condition = match self {
+                    Instruction::FBEQ(_, _, _) => 0b00,
+                    Instruction::FBNE(_, _, _) => 0b01,
+                    Instruction::FBSM(_, _, _) => 0b10,
+                    _ => 0b11,
+                };
+                let mut res: u32 = 0b11011;
+                res <<= 2;
+                res |= condition; // 2 bits for the condition
+                res <<= 2;
+                res |= r as u32; // 2 bits for the floating-point register
+                res <<= 12;
+                res |= Into::<u12>::into(p); // 12 bits for the parameter
+                res <<= 3;
+                res |= Into::<u3>::into(lbl) as u32 & 0b111; // 3 bits for the label
+                res <<= 8; // 8 bits to get to 32 bits
+                res
+            }
+            Instruction::ITOF(r, p) => {
+                let mut res: u32 = 0b11100;
+                res <<= 1; // 1 bit for the direction, 0 from an integer
+                res <<= 2;
+                res |= r as u32; // 2 bits for the floating-point register
+                res <<= 12;
+                res |= Into::<u12>::into(p); // 12 bits for the parameter
+                res <<= 12; // 12 bits to get to 32 bits
+                res
+            }
+            Instruction::FTOI(r, s) => {
+                let mut res: u32 = 0b11100;
+                res <<= 1;
+                res |= 0b1; // 1 bit for the direction, 1 to an integer
+                res <<= 2;
+                res |= r as u32; // 2 bits for the register
+                res <<= 2;
+                res |= s as u32; // 2 bits for the floating-point register
+                res <<= 22; // 22 bits to get to 32 bits
+                res
+            }
+            Instruction::FOUT(p) => {
+                let mut res: u32 = 0b11101;
+                res <<= 12;
+                res |= Into::<u12>::into(p); // 12 bits for the parameter
+                res <<= 15; // 15 bits to get to 32 bits
+                res
+            }
+        }
+    }
+}
+
+// how OUT writes a value, stored in the output mode bits of the instruction
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum OutputFormat {
+    Decimal,
+    Char,
+    Hex,
+    Binary,
+    Signed,
+}
+
+impl FromStr for OutputFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dec" => Ok(OutputFormat::Decimal),
+            "char" => Ok(OutputFormat::Char),
+            "hex" => Ok(OutputFormat::Hex),
+            "bin" => Ok(OutputFormat::Binary),
+            "signed" => Ok(OutputFormat::Signed),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputFormat::Decimal => write!(f, "dec"),
+            OutputFormat::Char => write!(f, "char"),
+            OutputFormat::Hex => write!(f, "hex"),
+            OutputFormat::Binary => write!(f, "bin"),
+            OutputFormat::Signed => write!(f, "signed"),
+        }
+    }
+}
+
+impl OutputFormat {
+    // text written for a value, characters are written without a new line,
+    // None when the value is not a valid character
+    pub fn format(&self, value: u64) -> Option<String> {
+        match self {
+            OutputFormat::Decimal => Some(format!("{}\n", value)),
+            OutputFormat::Char => u32::try_from(value)
+                .ok()
+                .and_then(char::from_u32)
+                .map(|c| c.to_string()),
+            OutputFormat::Hex => Some(format!("0x{:X}\n", value)),
+            OutputFormat::Binary => Some(format!("0b{:b}\n", value)),
+            OutputFormat::Signed => Some(format!("{}\n", value as i64)),
+        }
+    }
+}
+
+// the mode 0b010 is used by PRINTS
+impl TryFrom<u32> for OutputFormat {
+    type Error = String;
+
+    fn try_from(i: u32) -> Result<Self, Self::Error> {
+        match i {
+            0b000 => Ok(OutputFormat::Decimal),
+            0b001 => Ok(OutputFormat::Char),
+            0b011 => Ok(OutputFormat::Hex),
+            0b100 => Ok(OutputFormat::Binary),
+            0b101 => Ok(OutputFormat::Signed),
+            _ => Err("Invalid output mode".to_string()),
+        }
+    }
+}
+
+impl From<OutputFormat> for u32 {
+    fn from(format: OutputFormat) -> u32 {
+        match format {
+            OutputFormat::Decimal => 0b000,
+            OutputFormat::Char => 0b001,
+            OutputFormat::Hex => 0b011,
+            OutputFormat::Binary => 0b100,
+            OutputFormat::Signed => 0b101,
         }
     }
 }
@@ -290,43 +801,124 @@ pub enum Parameter {
 }
 
 impl Parameter {
-    pub fn from_str(s: &str, variable_names: &mut AddressNames) -> Self {
+    pub fn from_str(s: &str, variable_names: &mut AddressNames) -> Result<Self, String> {
         // check if the string is a register
-        match s.parse::<Register>() {
-            Ok(_) => return Parameter::Register(s.parse::<Register>().unwrap().into()),
-            Err(_) => (),
+        if let Ok(register) = s.parse::<Register>() {
+            return Ok(Parameter::Register(register));
         }
         // check if the string is a constant
-        match s.parse::<i32>() {
-            Ok(_) => {
-                return Parameter::Constant(s.parse::<i32>().unwrap() as u12);
-            }
-            Err(_) => (),
+        if let Ok(constant) = s.parse::<i32>() {
+            return Ok(Parameter::Constant(constant as u12));
         }
         // else it's a variable
-        return Parameter::Variable(Variable::from_str(s, variable_names).into());
+        Variable::from_str(s, variable_names).map(Parameter::Variable)
+    }
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Parameter::Register(r) => write!(f, "{}", r),
+            Parameter::Variable(v) => write!(f, "{:?}", v),
+            Parameter::Constant(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+// operand of an instruction of the FPU: a floating-point register, an address holding
+// the bits of a f32, or an integer constant
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum FloatParameter {
+    Register(FloatRegister),
+    Variable(Variable),
+    Constant(u12),
+}
+
+impl FloatParameter {
+    pub fn from_str(s: &str, variable_names: &mut AddressNames) -> Result<Self, String> {
+        if let Ok(register) = s.parse::<FloatRegister>() {
+            return Ok(FloatParameter::Register(register));
+        }
+        match Parameter::from_str(s, variable_names)? {
+            Parameter::Variable(variable) => Ok(FloatParameter::Variable(variable)),
+            Parameter::Constant(constant) => Ok(FloatParameter::Constant(constant)),
+            Parameter::Register(_) => Err(format!("{} is not a floating-point register", s)),
+        }
+    }
+}
+
+impl fmt::Display for FloatParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FloatParameter::Register(r) => write!(f, "{}", r),
+            FloatParameter::Variable(v) => write!(f, "{:?}", v),
+            FloatParameter::Constant(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+// the operands of the FPU are encoded as the other parameters, with the number of
+// the floating-point register in place of the one of the register
+impl TryFrom<Parameter> for FloatParameter {
+    type Error = String;
+
+    fn try_from(parameter: Parameter) -> Result<Self, Self::Error> {
+        match parameter {
+            Parameter::Register(r) => {
+                Ok(FloatParameter::Register(FloatRegister::try_from(r as u2)?))
+            }
+            Parameter::Variable(v) => Ok(FloatParameter::Variable(v)),
+            Parameter::Constant(c) => Ok(FloatParameter::Constant(c)),
+        }
     }
 }
 
-impl From<u12> for Parameter {
-    fn from(i: u12) -> Self {
+impl From<FloatParameter> for Parameter {
+    fn from(parameter: FloatParameter) -> Self {
+        match parameter {
+            // there are more registers than floating-point registers
+            FloatParameter::Register(r) => Parameter::Register(REGISTERS[r as usize]),
+            FloatParameter::Variable(v) => Parameter::Variable(v),
+            FloatParameter::Constant(c) => Parameter::Constant(c),
+        }
+    }
+}
+
+impl TryFrom<u12> for FloatParameter {
+    type Error = String;
+
+    fn try_from(i: u12) -> Result<Self, Self::Error> {
+        FloatParameter::try_from(Parameter::try_from(i)?)
+    }
+}
+
+impl From<FloatParameter> for u12 {
+    fn from(val: FloatParameter) -> Self {
+        Parameter::from(val).into()
+    }
+}
+
+impl TryFrom<u12> for Parameter {
+    type Error = String;
+
+    fn try_from(i: u12) -> Result<Self, Self::Error> {
         let param_type = i >> 10;
         match param_type {
             0b00 => {
                 let register = ((i >> 8) & 0b11) as u2;
-                let register = Into::<Register>::into(register);
-                return Parameter::Register(register);
+                let register = Register::try_from(register)?;
+                Ok(Parameter::Register(register))
             }
             0b01 => {
                 let variable = i & 0b1111111111;
                 let variable = Into::<Variable>::into(variable as u10);
-                return Parameter::Variable(variable);
+                Ok(Parameter::Variable(variable))
             }
             0b10 => {
                 let constant = i & 0b1111111111;
-                return Parameter::Constant(constant);
+                Ok(Parameter::Constant(constant))
             }
-            _ => panic!("Invalid parameter"),
+            _ => Err("Invalid parameter".to_string()),
         }
     }
 }
@@ -363,46 +955,125 @@ pub enum Register {
     T1,
     T2,
     T3,
+    T4,
+    T5,
+    T6,
+    T7,
+    T8,
+    T9,
+    T10,
+    T11,
+    T12,
+    T13,
+    T14,
+    T15,
 }
 
+// every register in the order of their number, a machine has the first ones
+pub const REGISTERS: [Register; 16] = [
+    Register::T0,
+    Register::T1,
+    Register::T2,
+    Register::T3,
+    Register::T4,
+    Register::T5,
+    Register::T6,
+    Register::T7,
+    Register::T8,
+    Register::T9,
+    Register::T10,
+    Register::T11,
+    Register::T12,
+    Register::T13,
+    Register::T14,
+    Register::T15,
+];
+
 // impt from_str for Register
 impl FromStr for Register {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "T0" => Ok(Register::T0),
-            "T1" => Ok(Register::T1),
-            "T2" => Ok(Register::T2),
-            "T3" => Ok(Register::T3),
-            _ => Err(()),
-        }
+        REGISTERS
+            .into_iter()
+            .find(|r| format!("{:?}", r) == s)
+            .ok_or(())
     }
 }
 
-impl From<u2> for Register {
-    fn from(i: u2) -> Self {
-        match i {
-            0b00 => Register::T0,
-            0b01 => Register::T1,
-            0b10 => Register::T2,
-            0b11 => Register::T3,
-            _ => panic!("Invalid register"),
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl TryFrom<u2> for Register {
+    type Error = String;
+
+    fn try_from(i: u2) -> Result<Self, Self::Error> {
+        match REGISTERS.get(i as usize) {
+            Some(register) => Ok(*register),
+            None => Err("Invalid register".to_string()),
         }
     }
 }
 
 impl Into<u2> for Register {
     fn into(self) -> u2 {
-        match self {
-            Register::T0 => 0b00,
-            Register::T1 => 0b01,
-            Register::T2 => 0b10,
-            Register::T3 => 0b11,
+        self as u2
+    }
+}
+
+// registers of the FPU, holding IEEE-754 single precision values
+#[derive(Eq, Hash, PartialEq, Debug, Copy, Clone)]
+pub enum FloatRegister {
+    F0,
+    F1,
+    F2,
+    F3,
+}
+
+pub const FLOAT_REGISTERS: [FloatRegister; 4] = [
+    FloatRegister::F0,
+    FloatRegister::F1,
+    FloatRegister::F2,
+    FloatRegister::F3,
+];
+
+impl FromStr for FloatRegister {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FLOAT_REGISTERS
+            .into_iter()
+            .find(|r| format!("{:?}", r) == s)
+            .ok_or(())
+    }
+}
+
+impl fmt::Display for FloatRegister {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl TryFrom<u2> for FloatRegister {
+    type Error = String;
+
+    fn try_from(i: u2) -> Result<Self, Self::Error> {
+        match FLOAT_REGISTERS.get(i as usize) {
+            Some(register) => Ok(*register),
+            None => Err("Invalid floating-point register".to_string()),
         }
     }
 }
 
+impl From<FloatRegister> for u2 {
+    fn from(val: FloatRegister) -> Self {
+        val as u2
+    }
+}
+
 #[derive(Debug)]
 pub struct AddressNames(Vec<String>);
 
@@ -411,29 +1082,39 @@ impl AddressNames {
         AddressNames(Vec::new())
     }
 
-    pub fn add(&mut self, s: &str) -> u16 {
-        self.0.push(s.to_string());
-        if (self.0.len() as u16 - 1) > 0b1111111111 {
-            panic!("Too many variables");
+    pub fn add(&mut self, s: &str) -> Result<u16, String> {
+        // the size of the memory of the machine is checked once the program is assembled
+        if self.0.len() >= u16::MAX as usize {
+            return Err("Too many variables".to_string());
         }
-        if self.0.iter().filter(|x| *x == s).count() > 1 {
-            panic!("Variable already exists");
+        if self.contains(s) {
+            return Err("Variable already exists".to_string());
         }
         // check if variable name is valid
         if s.chars().any(|c| !c.is_alphanumeric()) {
-            panic!("You can only use alphanumeric characters in variable names");
+            return Err("You can only use alphanumeric characters in variable names".to_string());
         }
         // check if variable name is not a register
-        match s.parse::<Register>() {
-            Ok(_) => panic!("Variable name cannot be a register"),
-            Err(_) => (),
+        if s.parse::<Register>().is_ok() || s.parse::<FloatRegister>().is_ok() {
+            return Err("Variable name cannot be a register".to_string());
         }
-        return self.0.len() as u16 - 1;
+        self.0.push(s.to_string());
+        Ok(self.0.len() as u16 - 1)
     }
 
     fn contains(&self, s: &str) -> bool {
         self.0.contains(&s.to_string())
     }
+
+    // address of a name if it exists
+    pub fn position(&self, s: &str) -> Option<u16> {
+        self.0.iter().position(|x| x == s).map(|i| i as u16)
+    }
+
+    // name at an address if it exists
+    pub fn name(&self, address: u16) -> Option<&str> {
+        self.0.get(address as usize).map(|s| s.as_str())
+    }
 }
 
 #[derive(Eq, Copy, Clone)]
@@ -443,21 +1124,22 @@ pub struct Variable {
 }
 
 impl Variable {
-    pub fn new(s: &str, address_names: &mut AddressNames) -> Self {
+    pub fn new(s: &str, address_names: &mut AddressNames) -> Result<Self, String> {
         // check that the variable name does not exist
         if address_names.contains(s) {
-            panic!("Address already exists");
+            return Err("Address already exists".to_string());
         }
+        let name = address_names.add(s)?;
         // make the lifetime static
         let owned_string: String = s.to_string();
         let static_string: &'static str = Box::leak(owned_string.into_boxed_str());
-        return Variable {
-            name: address_names.add(s),
+        Ok(Variable {
+            name,
             alias: Some(static_string),
-        };
+        })
     }
 
-    pub fn from_str(s: &str, address_names: &mut AddressNames) -> Self {
+    pub fn from_str(s: &str, address_names: &mut AddressNames) -> Result<Self, String> {
         {
             // split if there is a + in the string
             let s: Vec<&str> = if s.contains("+") {
@@ -475,33 +1157,44 @@ impl Variable {
             if s.len() > 1 {
                 // check that the variable name exists
                 if !address_names.contains(s[0]) {
-                    panic!("Address does not exist");
+                    return Err("Address does not exist".to_string());
                 }
                 // get the the value of the offset
-                let offset: u16 = s[1].parse().unwrap();
+                let offset: u16 = match s[1].parse() {
+                    Ok(offset) => offset,
+                    Err(_) => return Err(format!("Invalid offset {}", s[1])),
+                };
                 // make the lifetime static
                 let mut owned_string: String = s[0].to_string();
-                owned_string.push_str("+");
+                owned_string.push('+');
                 owned_string.push_str(s[1]);
                 let static_string: &'static str = Box::leak(owned_string.into_boxed_str());
-                return Variable {
-                    name: address_names.0.iter().position(|x| x == s[0]).unwrap() as u16 + offset,
-                    alias: Some(static_string),
+                let name = match address_names.position(s[0]) {
+                    Some(name) => name.checked_add(offset),
+                    None => None,
                 };
+                match name {
+                    Some(name) => Ok(Variable {
+                        name,
+                        alias: Some(static_string),
+                    }),
+                    None => Err(format!("Invalid offset {}", s[1])),
+                }
             } else if s.len() == 1 {
                 // check that the variable name exists
-                if !address_names.contains(s[0]) {
-                    panic!("Address does not exist");
-                }
+                let name = match address_names.position(s[0]) {
+                    Some(name) => name,
+                    None => return Err("Address does not exist".to_string()),
+                };
                 // make the lifetime static
                 let owned_string: String = s[0].to_string();
                 let static_string: &'static str = Box::leak(owned_string.into_boxed_str());
-                return Variable {
-                    name: address_names.0.iter().position(|x| x == s[0]).unwrap() as u16,
+                Ok(Variable {
+                    name,
                     alias: Some(static_string),
-                };
+                })
             } else {
-                panic!("Invalid variable address");
+                Err("Invalid variable address".to_string())
             }
         }
     }
@@ -550,18 +1243,19 @@ pub struct Label {
 }
 
 impl Label {
-    pub fn from_str(s: &str, label_names: &mut AddressNames) -> Self {
-        {
-            // check that the label name exists
-            if !label_names.contains(s) {
-                return Label {
-                    name: label_names.add(s),
-                };
-            }
-            return Label {
-                name: label_names.0.iter().position(|x| x == s).unwrap() as u16,
-            };
-        }
+    pub fn from_str(s: &str, label_names: &mut AddressNames) -> Result<Self, String> {
+        // the label is added the first time it is used
+        let name = match label_names.position(s) {
+            Some(name) => name,
+            None => label_names.add(s)?,
+        };
+        Ok(Label { name })
+    }
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "L{}", self.name)
     }
 }
 
@@ -1,27 +1,59 @@
-use crate::enums::{AddressNames, Extension, Instruction, Label, Parameter, Register, Variable};
+use crate::arch::{decode_wide, encode_wide, Architecture, CLASSIC};
+use crate::enums::{
+    AddressNames, Extension, FloatParameter, FloatRegister, Instruction, Label, OutputFormat,
+    Parameter, Register, Variable,
+};
+use crate::optimize::{optimize, Change};
+use crate::preprocess::{preprocess, Location};
+use crate::program::AssemblyError;
+use crate::pseudo::expand;
 use regex::Regex;
+use std::collections::HashSet;
 use std::io::Read;
+use std::path::Path;
 use std::str::FromStr;
 
 // define macros to convert a string to a Instruction with a parameter and a register
 macro_rules! str_to_instruction {
     ($instruction:ident, $line:ident, $variable_names: ident) => {
-        let register = $line.next().unwrap();
-        let parameter = $line.next().unwrap();
-        return Instruction::$instruction(
-            Register::from_str(register).unwrap(),
-            Parameter::from_str(parameter, $variable_names),
-        )
+        let register = operand(&mut $line)?;
+        let parameter = operand(&mut $line)?;
+        return Ok(Instruction::$instruction(
+            register_from_str(register)?,
+            Parameter::from_str(parameter, $variable_names)?,
+        ))
     };
     ($instruction:ident, $line:ident, $variable_names: ident, $label_names: ident) => {
-        let parameter_1 = $line.next().unwrap();
-        let parameter_2 = $line.next().unwrap();
-        let label = $line.next().unwrap();
-        return Instruction::$instruction(
-            Parameter::from_str(parameter_1, $variable_names),
-            Parameter::from_str(parameter_2, $variable_names),
-            Label::from_str(label, $label_names),
-        );
+        let parameter_1 = operand(&mut $line)?;
+        let parameter_2 = operand(&mut $line)?;
+        let label = operand(&mut $line)?;
+        return Ok(Instruction::$instruction(
+            Parameter::from_str(parameter_1, $variable_names)?,
+            Parameter::from_str(parameter_2, $variable_names)?,
+            Label::from_str(label, $label_names)?,
+        ));
+    };
+}
+
+// same as str_to_instruction for the instructions of the FPU
+macro_rules! str_to_float_instruction {
+    ($instruction:ident, $line:ident, $variable_names: ident) => {
+        let register = operand(&mut $line)?;
+        let parameter = operand(&mut $line)?;
+        return Ok(Instruction::$instruction(
+            float_register_from_str(register)?,
+            FloatParameter::from_str(parameter, $variable_names)?,
+        ))
+    };
+    ($instruction:ident, $line:ident, $variable_names: ident, $label_names: ident) => {
+        let register = operand(&mut $line)?;
+        let parameter = operand(&mut $line)?;
+        let label = operand(&mut $line)?;
+        return Ok(Instruction::$instruction(
+            float_register_from_str(register)?,
+            FloatParameter::from_str(parameter, $variable_names)?,
+            Label::from_str(label, $label_names)?,
+        ));
     };
 }
 
@@ -29,75 +61,153 @@ macro_rules! bin_to_instruction {
     ($instruction:ident, $line:ident) => {
         let register = ($line >> 25) & 0b11; // get the register
         let parameter = ($line >> 13) & 0b111111111111; // get the parameter
-        return Instruction::$instruction(Register::from(register as u8), Parameter::from(parameter))
+        return Ok(Instruction::$instruction(
+            Register::try_from(register as u8)?,
+            Parameter::try_from(parameter)?,
+        ))
     };
     // overload macro for instructions with 2 parameters and a label
     ($instruction:ident, $line:ident, $_:ident) => {
         let parameter_1 = ($line >> 15) & 0b111111111111; // get the first parameter
         let parameter_2 = ($line >> 3) & 0b111111111111; // get the second parameter
         let label = $line & 0b111; // get the label
-        return Instruction::$instruction(
-            Parameter::from(parameter_1),
-            Parameter::from(parameter_2),
+        return Ok(Instruction::$instruction(
+            Parameter::try_from(parameter_1)?,
+            Parameter::try_from(parameter_2)?,
             Label::from(label as u8),
-        );
+        ));
     };
 }
 
+// next operand of an instruction
+fn operand<'a>(line: &mut std::str::SplitWhitespace<'a>) -> Result<&'a str, String> {
+    line.next().ok_or("Missing operand".to_string())
+}
+
+fn register_from_str(s: &str) -> Result<Register, String> {
+    Register::from_str(s).map_err(|_| format!("Invalid register {}", s))
+}
+
+fn float_register_from_str(s: &str) -> Result<FloatRegister, String> {
+    FloatRegister::from_str(s).map_err(|_| format!("Invalid floating-point register {}", s))
+}
+
+// number of bits of a shift
+fn shift_from_str(s: &str) -> Result<u16, String> {
+    s.parse::<u16>().map_err(|_| format!("Invalid constant {}", s))
+}
+
 pub enum LineType {
     String(String),
     Bin(u32),
+    // instruction of a machine using the 64-bit encoding
+    Wide(u64),
 }
 
 impl LineType {
+    // split the source of a program in lines
+    pub fn from_text(text: &str) -> Vec<LineType> {
+        text.split('\n')
+            .map(|line| LineType::String(line.to_string()))
+            .collect()
+    }
+
+    // split a binary program in instructions of 32 bits, or of 64 bits after the header
+    // of a machine using the wide encoding. The header is given back as its #CONFIG line.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Vec<LineType>, String> {
+        let mut lines: Vec<LineType> = Vec::new();
+        let mut bytes = bytes;
+        let mut architecture = CLASSIC;
+        if bytes.len() >= 4 {
+            let word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            if Architecture::is_header(word) {
+                architecture = match Architecture::from_header(word) {
+                    Ok(architecture) => architecture,
+                    Err(e) => return Err(format!("Invalid header: {}", e)),
+                };
+                lines.push(LineType::String(format!("#CONFIG {}", architecture)));
+                bytes = &bytes[4..];
+            }
+        }
+        let size = if architecture.is_wide() { 8 } else { 4 };
+        for chunk in bytes.chunks(size) {
+            if chunk.len() != size {
+                return Err("Invalid file format".to_string());
+            }
+            if architecture.is_wide() {
+                let mut word = [0; 8];
+                word.copy_from_slice(chunk);
+                lines.push(LineType::Wide(u64::from_be_bytes(word)));
+            } else {
+                // convert the 4 bytes into a u32
+                lines.push(LineType::Bin(u32::from_be_bytes([
+                    chunk[0], chunk[1], chunk[2], chunk[3],
+                ])));
+            }
+        }
+        Ok(lines)
+    }
+
+    // architecture given by a #CONFIG line, None for the other lines
+    pub fn config(&self) -> Option<Result<Architecture, String>> {
+        match self {
+            LineType::String(line) => Architecture::from_directive(line),
+            _ => None,
+        }
+    }
+
     pub fn translate(
         &self,
         category: &LineCategory,
         variable_names: &mut AddressNames,
         label_names: &mut AddressNames,
-    ) -> Instruction {
+    ) -> Result<Instruction, String> {
+        if let LineType::Wide(word) = self {
+            return decode_wide(*word, category);
+        }
         // export categories
         if *category == LineCategory::CODE {
             match self {
                 LineType::String(line) => {
-                    // remove leading and trailing whitespaces
-                    let line = line.trim();
+                    // remove the comment at the end of the line and the whitespaces around
+                    let line = line.split(';').next().unwrap().trim();
                     // check if line is a label
                     if line.ends_with(":") {
                         let lbl_name = line.replace(":", "");
-                        return Instruction::LABEL(Label::from_str(lbl_name.as_str(), label_names));
+                        let label = Label::from_str(lbl_name.as_str(), label_names)?;
+                        return Ok(Instruction::LABEL(label));
                     }
-                    let mut line = line.split(" ");
-                    let instruction = line.next().unwrap();
+                    let mut line = line.split_whitespace();
+                    let instruction = operand(&mut line)?;
                     match instruction {
                         "LDA" => {
                             str_to_instruction!(LDA, line, variable_names);
                         }
                         "STR" => {
-                            let variable = line.next().unwrap();
-                            let parameter = line.next().unwrap();
-                            return Instruction::STR(
-                                Variable::from_str(variable, variable_names),
-                                Parameter::from_str(parameter, variable_names),
-                            );
+                            let variable = operand(&mut line)?;
+                            let parameter = operand(&mut line)?;
+                            Ok(Instruction::STR(
+                                Variable::from_str(variable, variable_names)?,
+                                Parameter::from_str(parameter, variable_names)?,
+                            ))
                         }
                         "PUSH" => {
-                            let parameter = line.next().unwrap();
-                            return Instruction::PUSH(Parameter::from_str(
+                            let parameter = operand(&mut line)?;
+                            Ok(Instruction::PUSH(Parameter::from_str(
                                 parameter,
                                 variable_names,
-                            ));
+                            )?))
                         }
                         "POP" => {
-                            let register = line.next().unwrap();
-                            return Instruction::POP(Register::from_str(register).unwrap());
+                            let register = operand(&mut line)?;
+                            Ok(Instruction::POP(register_from_str(register)?))
                         }
                         "OR" => {
                             str_to_instruction!(OR, line, variable_names);
                         }
                         "NOT" => {
-                            let register = line.next().unwrap();
-                            return Instruction::NOT(Register::from_str(register).unwrap());
+                            let register = operand(&mut line)?;
+                            Ok(Instruction::NOT(register_from_str(register)?))
                         }
                         "AND" => {
                             str_to_instruction!(AND, line, variable_names);
@@ -118,12 +228,12 @@ impl LineType {
                             str_to_instruction!(MOD, line, variable_names);
                         }
                         "INC" => {
-                            let register = line.next().unwrap();
-                            return Instruction::INC(Register::from_str(register).unwrap());
+                            let register = operand(&mut line)?;
+                            Ok(Instruction::INC(register_from_str(register)?))
                         }
                         "DEC" => {
-                            let register = line.next().unwrap();
-                            return Instruction::DEC(Register::from_str(register).unwrap());
+                            let register = operand(&mut line)?;
+                            Ok(Instruction::DEC(register_from_str(register)?))
                         }
                         "BEQ" => {
                             str_to_instruction!(BEQ, line, variable_names, label_names);
@@ -138,40 +248,137 @@ impl LineType {
                             str_to_instruction!(BSM, line, variable_names, label_names);
                         }
                         "JMP" => {
-                            let label = line.next().unwrap();
-                            return Instruction::JMP(Label::from_str(label, label_names));
+                            let label = operand(&mut line)?;
+                            Ok(Instruction::JMP(Label::from_str(label, label_names)?))
                         }
                         "SRL" => {
-                            let register = line.next().unwrap();
-                            let constant = line.next().unwrap();
-                            return Instruction::SRL(
-                                Register::from_str(register).unwrap(),
-                                constant.parse::<u16>().unwrap(),
-                            );
+                            let register = operand(&mut line)?;
+                            let constant = operand(&mut line)?;
+                            Ok(Instruction::SRL(
+                                register_from_str(register)?,
+                                shift_from_str(constant)?,
+                            ))
                         }
                         "SRR" => {
-                            let register = line.next().unwrap();
-                            let constant = line.next().unwrap();
-                            return Instruction::SRR(
-                                Register::from_str(register).unwrap(),
-                                constant.parse::<u16>().unwrap(),
-                            );
+                            let register = operand(&mut line)?;
+                            let constant = operand(&mut line)?;
+                            Ok(Instruction::SRR(
+                                register_from_str(register)?,
+                                shift_from_str(constant)?,
+                            ))
                         }
                         "HLT" => {
-                            return Instruction::HLT;
+                            // the exit code is optional
+                            let code = match line.next() {
+                                Some(parameter) => {
+                                    Some(Parameter::from_str(parameter, variable_names)?)
+                                }
+                                None => None,
+                            };
+                            Ok(Instruction::HLT(code))
                         }
                         "IN" => {
-                            let parameter = line.next().unwrap();
-                            return Instruction::IN(Parameter::from_str(parameter, variable_names));
+                            let parameter = operand(&mut line)?;
+                            let parameter = Parameter::from_str(parameter, variable_names)?;
+                            Ok(Instruction::IN(parameter))
                         }
                         "OUT" => {
-                            let parameter = line.next().unwrap();
-                            return Instruction::OUT(Parameter::from_str(
+                            let parameter = operand(&mut line)?;
+                            // the format of the output is optional
+                            let format = match line.next() {
+                                Some(format) => match OutputFormat::from_str(format) {
+                                    Ok(format) => format,
+                                    Err(_) => {
+                                        return Err(format!("Invalid output format {}", format))
+                                    }
+                                },
+                                None => OutputFormat::Decimal,
+                            };
+                            Ok(Instruction::OUT(
+                                Parameter::from_str(parameter, variable_names)?,
+                                format,
+                            ))
+                        }
+                        "GETC" => {
+                            let parameter = operand(&mut line)?;
+                            Ok(Instruction::GETC(Parameter::from_str(
+                                parameter,
+                                variable_names,
+                            )?))
+                        }
+                        "PUTC" => {
+                            let parameter = operand(&mut line)?;
+                            Ok(Instruction::OUT(
+                                Parameter::from_str(parameter, variable_names)?,
+                                OutputFormat::Char,
+                            ))
+                        }
+                        "PRINTS" => {
+                            let variable = operand(&mut line)?;
+                            Ok(Instruction::PRINTS(Variable::from_str(
+                                variable,
+                                variable_names,
+                            )?))
+                        }
+                        "FLDA" => {
+                            str_to_float_instruction!(FLDA, line, variable_names);
+                        }
+                        "FSTR" => {
+                            let variable = operand(&mut line)?;
+                            let register = operand(&mut line)?;
+                            Ok(Instruction::FSTR(
+                                Variable::from_str(variable, variable_names)?,
+                                float_register_from_str(register)?,
+                            ))
+                        }
+                        "FADD" => {
+                            str_to_float_instruction!(FADD, line, variable_names);
+                        }
+                        "FSUB" => {
+                            str_to_float_instruction!(FSUB, line, variable_names);
+                        }
+                        "FMUL" => {
+                            str_to_float_instruction!(FMUL, line, variable_names);
+                        }
+                        "FDIV" => {
+                            str_to_float_instruction!(FDIV, line, variable_names);
+                        }
+                        "FBEQ" => {
+                            str_to_float_instruction!(FBEQ, line, variable_names, label_names);
+                        }
+                        "FBNE" => {
+                            str_to_float_instruction!(FBNE, line, variable_names, label_names);
+                        }
+                        "FBSM" => {
+                            str_to_float_instruction!(FBSM, line, variable_names, label_names);
+                        }
+                        "FBBG" => {
+                            str_to_float_instruction!(FBBG, line, variable_names, label_names);
+                        }
+                        "ITOF" => {
+                            let register = operand(&mut line)?;
+                            let parameter = operand(&mut line)?;
+                            Ok(Instruction::ITOF(
+                                float_register_from_str(register)?,
+                                Parameter::from_str(parameter, variable_names)?,
+                            ))
+                        }
+                        "FTOI" => {
+                            let register = operand(&mut line)?;
+                            let source = operand(&mut line)?;
+                            Ok(Instruction::FTOI(
+                                register_from_str(register)?,
+                                float_register_from_str(source)?,
+                            ))
+                        }
+                        "FOUT" => {
+                            let parameter = operand(&mut line)?;
+                            Ok(Instruction::FOUT(FloatParameter::from_str(
                                 parameter,
                                 variable_names,
-                            ));
+                            )?))
                         }
-                        _ => panic!("Invalid instruction"),
+                        _ => Err("Invalid instruction".to_string()),
                     }
                 }
                 LineType::Bin(line) => {
@@ -182,19 +389,19 @@ impl LineType {
                         }
                         0b00001 => {
                             let variable = ((line >> 17) & 0b1111111111) as u16; // get the variable name
-                            let parameter = ((line >> 5) & 0b111111111111) as u32; // get the parameter
-                            return Instruction::STR(
+                            let parameter = (line >> 5) & 0b111111111111 ; // get the parameter
+                            Ok(Instruction::STR(
                                 Variable::from(variable),
-                                Parameter::from(parameter),
-                            );
+                                Parameter::try_from(parameter)?,
+                            ))
                         }
                         0b00010 => {
-                            let parameter = ((line >> 15) & 0b111111111111) as u32; // get the parameter
-                            return Instruction::PUSH(Parameter::from(parameter));
+                            let parameter = (line >> 15) & 0b111111111111 ; // get the parameter
+                            Ok(Instruction::PUSH(Parameter::try_from(parameter)?))
                         }
                         0b00011 => {
                             let register = ((line >> 25) & 0b11) as u8; // get the register
-                            return Instruction::POP(Register::from(register));
+                            Ok(Instruction::POP(Register::try_from(register)?))
                         }
                         0b00100 => {
                             bin_to_instruction!(AND, line);
@@ -204,7 +411,7 @@ impl LineType {
                         }
                         0b00110 => {
                             let register = ((line >> 25) & 0b11) as u8; // get the register
-                            return Instruction::NOT(Register::from(register));
+                            Ok(Instruction::NOT(Register::try_from(register)?))
                         }
                         0b00111 => {
                             bin_to_instruction!(ADD, line);
@@ -223,11 +430,11 @@ impl LineType {
                         }
                         0b01100 => {
                             let register = ((line >> 25) & 0b11) as u8; // get the register
-                            return Instruction::INC(Register::from(register));
+                            Ok(Instruction::INC(Register::try_from(register)?))
                         }
                         0b01101 => {
                             let register = ((line >> 25) & 0b11) as u8; // get the register
-                            return Instruction::DEC(Register::from(register));
+                            Ok(Instruction::DEC(Register::try_from(register)?))
                         }
                         0b01110 => {
                             bin_to_instruction!(BEQ, line, line);
@@ -243,61 +450,151 @@ impl LineType {
                         }
                         0b10010 => {
                             let label = (line >> 24) & 0b111; // get the label
-                            return Instruction::JMP(Label::from(label as u8));
+                            Ok(Instruction::JMP(Label::from(label as u8)))
                         }
                         0b10011 => {
                             let register = ((line >> 25) & 0b11) as u8; // get the register
                             let constant = ((line >> 15) & 0b1111111111) as u16; // get the constant
-                            return Instruction::SRL(Register::from(register), constant);
+                            Ok(Instruction::SRL(Register::try_from(register)?, constant))
                         }
                         0b10100 => {
-                            let register = ((line >> 25) & 0b11111) as u8; // get the register
+                            let register = ((line >> 25) & 0b11) as u8; // get the register
                             let constant = ((line >> 15) & 0b1111111111) as u16; // get the constant
-                            return Instruction::SRR(Register::from(register), constant);
+                            Ok(Instruction::SRR(Register::try_from(register)?, constant))
                         }
                         0b10101 => {
-                            return Instruction::HLT;
+                            if (line >> 26) & 0b1 == 0 {
+                                return Ok(Instruction::HLT(None));
+                            }
+                            let parameter = (line >> 14) & 0b111111111111 ; // get the exit code
+                            Ok(Instruction::HLT(Some(Parameter::try_from(parameter)?)))
                         }
                         0b10110 => {
                             let parameter = ((line >> 15) & 0b111111111111) as u32; // get the parameter
-                            return Instruction::IN(Parameter::from(parameter));
+                            let parameter = Parameter::try_from(parameter)?;
+                            let mode = (line >> 12) & 0b111; // get the input mode
+                            match mode {
+                                0b000 => Ok(Instruction::IN(parameter)),
+                                0b001 => Ok(Instruction::GETC(parameter)),
+                                _ => Err("Invalid input mode".to_string()),
+                            }
                         }
                         0b10111 => {
                             let parameter = ((line >> 15) & 0b111111111111) as u32; // get the parameter
-                            return Instruction::OUT(Parameter::from(parameter));
+                            let mode = (line >> 12) & 0b111; // get the output mode
+                            match mode {
+                                0b010 => match Parameter::try_from(parameter)? {
+                                    Parameter::Variable(variable) => {
+                                        Ok(Instruction::PRINTS(variable))
+                                    }
+                                    _ => Err("Invalid string address".to_string()),
+                                },
+                                _ => {
+                                    Ok(Instruction::OUT(
+                                        Parameter::try_from(parameter)?,
+                                        OutputFormat::try_from(mode)?,
+                                    ))
+                                }
+                            }
+                        }
+                        0b11000 => {
+                            let register = ((line >> 25) & 0b11) as u8; // get the floating-point register
+                            let parameter = (line >> 13) & 0b111111111111; // get the parameter
+                            Ok(Instruction::FLDA(
+                                FloatRegister::try_from(register)?,
+                                FloatParameter::try_from(parameter)?,
+                            ))
+                        }
+                        0b11001 => {
+                            let variable = ((line >> 17) & 0b1111111111) as u16; // get the variable name
+                            let register = ((line >> 15) & 0b11) as u8; // get the floating-point register
+                            Ok(Instruction::FSTR(
+                                Variable::from(variable),
+                                FloatRegister::try_from(register)?,
+                            ))
+                        }
+                        0b11010 => {
+                            let operation = (line >> 25) & 0b11; // get the operation
+                            let register = FloatRegister::try_from(((line >> 23) & 0b11) as u8)?;
+                            let parameter = (line >> 11) & 0b111111111111; // get the parameter
+                            let parameter = FloatParameter::try_from(parameter)?;
+                            Ok(match operation {
+                                0b00 => Instruction::FADD(register, parameter),
+                                0b01 => Instruction::FSUB(register, parameter),
+                                0b10 => Instruction::FMUL(register, parameter),
+                                _ => Instruction::FDIV(register, parameter),
+                            })
+                        }
+                        0b11011 => {
+                            let condition = (line >> 25) & 0b11; // get the condition
+                            let register = FloatRegister::try_from(((line >> 23) & 0b11) as u8)?;
+                            let parameter = (line >> 11) & 0b111111111111; // get the parameter
+                            let parameter = FloatParameter::try_from(parameter)?;
+                            let label = Label::from(((line >> 8) & 0b111) as u8);
+                            Ok(match condition {
+                                0b00 => Instruction::FBEQ(register, parameter, label),
+                                0b01 => Instruction::FBNE(register, parameter, label),
+                                0b10 => Instruction::FBSM(register, parameter, label),
+                                _ => Instruction::FBBG(register, parameter, label),
+                            })
+                        }
+                        0b11100 => {
+                            // the direction bit is set for a conversion to an integer
+                            if (line >> 26) & 0b1 == 0 {
+                                let register = ((line >> 24) & 0b11) as u8; // get the floating-point register
+                                let parameter = (line >> 12) & 0b111111111111; // get the parameter
+                                return Ok(Instruction::ITOF(
+                                    FloatRegister::try_from(register)?,
+                                    Parameter::try_from(parameter)?,
+                                ));
+                            }
+                            let register = ((line >> 24) & 0b11) as u8; // get the register
+                            let source = ((line >> 22) & 0b11) as u8; // get the floating-point register
+                            Ok(Instruction::FTOI(
+                                Register::try_from(register)?,
+                                FloatRegister::try_from(source)?,
+                            ))
+                        }
+                        0b11101 => {
+                            let parameter = (line >> 15) & 0b111111111111; // get the parameter
+                            Ok(Instruction::FOUT(FloatParameter::try_from(parameter)?))
                         }
                         0b11110 => {
                             let label = (line >> 24) & 0b111; // get the label
-                            return Instruction::LABEL(Label::from(label as u8));
+                            Ok(Instruction::LABEL(Label::from(label as u8)))
                         }
-                        _ => panic!("Invalid instruction"),
+                        _ => Err("Invalid instruction".to_string()),
                     }
                 }
+                LineType::Wide(_) => unreachable!(),
             }
         } else if *category == LineCategory::DATA {
             match self {
                 LineType::String(line) => {
+                    let (name, value) = match line.trim().split_once(char::is_whitespace) {
+                        Some((name, value)) => (name, value.trim()),
+                        None => return Err(format!("{} has no value", line.trim())),
+                    };
+                    // parse the value, the machine keeps the bits of its encoding
+                    let value = parse_value(value)?;
                     // check if line is a array
-                    if Regex::new(r"^[a-zA-Z0-9]+\[\d+\]").unwrap().is_match(line) {
-                        let (name, value) = line.split_at(line.find(" ").unwrap());
-                        let value = value.trim();
-                        let value =
-                            Into::<u32>::into(value.parse::<i32>().unwrap() as u32) & 0b1111111111; // parse the value and convert it to 10 bits integer
-                                                                                                    // split the name to get the variable name and the array length
+                    if Regex::new(r"^[a-zA-Z0-9]+\[\d+\]").unwrap().is_match(name) {
+                        // split the name to get the variable name and the array length
                         let (name, length) = name.split_at(name.find("[").unwrap());
                         let length = length.replace("[", "").replace("]", "");
-                        let length = length.parse::<u16>().unwrap();
-                        return Instruction::ARRAY(
-                            Variable::new(name, variable_names),
-                            value,
-                            length,
-                        );
+                        let length = match length.parse::<u16>() {
+                            Ok(length) => length,
+                            Err(_) => return Err(format!("Invalid array length {}", length)),
+                        };
+                        let variable = Variable::new(name, variable_names)?;
+                        // reserve the addresses of the rest of the array
+                        for i in 1..length {
+                            variable_names.add(&(variable.name + i).to_string())?;
+                        }
+                        Ok(Instruction::ARRAY(variable, value, length))
                     } else {
-                        let (name, value) = line.split_at(line.find(" ").unwrap());
-                        let value = value.trim();
-                        let value =
-                            Into::<u32>::into(value.parse::<i32>().unwrap() as u32) & 0b1111111111; // parse the value and convert it to 10 bits integer
-                        return Instruction::VARIABLE(Variable::new(name, variable_names), value);
+                        let variable = Variable::new(name, variable_names)?;
+                        Ok(Instruction::VARIABLE(variable, value))
                     }
                 }
                 LineType::Bin(line) => {
@@ -305,32 +602,58 @@ impl LineType {
                     let name = (line >> 21) & 0b1111111111; // get next 10 bits for variable name
                     if data_type == 0 {
                         let value = (line >> 11) & 0b1111111111; // get next 10 bits for variable value
-                        return Instruction::VARIABLE(Variable::from(name as u16), value);
+                        Ok(Instruction::VARIABLE(Variable::from(name as u16), value as u64))
                     } else {
                         let length = (line >> 11) & 0b1111111111; // get next 10 bits for array length
                         let value = (line >> 1) & 0b1111111111; // get last 10 bits for array value
-                        return Instruction::ARRAY(
+                        Ok(Instruction::ARRAY(
                             Variable::from(name as u16),
-                            value,
+                            value as u64,
                             length as u16,
-                        );
+                        ))
                     }
                 }
+                LineType::Wide(_) => unreachable!(),
             }
         } else {
-            panic!("Invalid category");
+            Err("Invalid category".to_string())
+        }
+    }
+
+    // translate a line that can stand for several instructions, such as a string literal
+    // or a pseudo-instruction
+    pub fn translate_all(
+        &self,
+        category: &LineCategory,
+        variable_names: &mut AddressNames,
+        label_names: &mut AddressNames,
+    ) -> Result<Vec<Instruction>, String> {
+        if let (LineCategory::DATA, LineType::String(line)) = (category, self) {
+            let line = line.trim();
+            if let Some((name, literal)) = line.split_once(' ') {
+                let literal = literal.trim();
+                if literal.len() >= 2 && literal.starts_with('"') && literal.ends_with('"') {
+                    return translate_string(name, &literal[1..literal.len() - 1], variable_names);
+                }
+            }
+        }
+        if let (LineCategory::CODE, LineType::String(line)) = (category, self) {
+            if let Some(lines) = expand(line)? {
+                return lines
+                    .into_iter()
+                    .map(|line| {
+                        LineType::String(line).translate(category, variable_names, label_names)
+                    })
+                    .collect();
+            }
         }
+        Ok(vec![self.translate(category, variable_names, label_names)?])
     }
 
     pub fn is_category(&self) -> bool {
         match self {
             LineType::String(line) => {
-                if line.starts_with("#DATA") {
-                    return true;
-                } else if line.starts_with("#CODE") {
-                    return true;
-                }
-                return false;
+                line.starts_with("#DATA") || line.starts_with("#CODE")
             }
             LineType::Bin(line) => {
                 // first 5 bits are 1
@@ -340,21 +663,22 @@ impl LineType {
                 }
                 return false;
             }
+            LineType::Wide(line) => line >> 59 == 0b11111,
         }
     }
 
-    pub fn get_category(&self) -> LineCategory {
+    pub fn get_category(&self) -> Result<LineCategory, String> {
         if !self.is_category() {
-            panic!("Line is not a category");
+            return Err("Line is not a category".to_string());
         }
         match self {
             LineType::String(line) => {
                 if line.starts_with("#DATA") {
-                    return LineCategory::DATA;
+                    return Ok(LineCategory::DATA);
                 } else if line.starts_with("#CODE") {
-                    return LineCategory::CODE;
+                    return Ok(LineCategory::CODE);
                 }
-                return LineCategory::NONE;
+                Ok(LineCategory::NONE)
             }
             LineType::Bin(line) => {
                 // first 5 bits are 1 and next 2 bits are category number
@@ -362,30 +686,28 @@ impl LineType {
                 // next 2 bits are category number
                 let line = line & 0b11;
                 if line == 0b00 {
-                    return LineCategory::DATA;
+                    Ok(LineCategory::DATA)
                 } else if line == 0b01 {
-                    return LineCategory::CODE;
+                    Ok(LineCategory::CODE)
                 } else {
-                    panic!("Invalid category");
+                    Err("Invalid category".to_string())
                 }
             }
+            // the category words of the wide encoding are the ones of 32 bits followed by 0
+            LineType::Wide(line) => LineType::Bin((line >> 32) as u32).get_category(),
         }
     }
 
     pub fn is_empty(&self) -> bool {
         match self {
             LineType::String(line) => {
-                if line.is_empty() {
-                    return true;
-                }
-                return false;
-            }
-            LineType::Bin(line) => {
-                if line == &0 {
+                if line.trim().is_empty() {
                     return true;
                 }
                 return false;
             }
+            // a zero word is an instruction or the declaration `V0 0` of the first variable
+            LineType::Bin(_) | LineType::Wide(_) => false,
         }
     }
 
@@ -399,13 +721,77 @@ impl LineType {
                 }
                 return false;
             }
-            LineType::Bin(_) => {
+            LineType::Bin(_) | LineType::Wide(_) => {
                 return false;
             }
         }
     }
 }
 
+// value of a #DATA declaration, a negative value is stored in two's complement
+fn parse_value(value: &str) -> Result<u64, String> {
+    match value.parse::<i64>() {
+        Ok(value) => Ok(value as u64),
+        Err(_) => match value.parse::<u64>() {
+            Ok(value) => Ok(value),
+            // the bits of a f32 do not fit in the encoding of a #DATA value
+            Err(_) if value.parse::<f32>().is_ok() => Err(format!(
+                "Invalid value {}, a #DATA value is an integer and a floating-point value is \
+                 computed with the FPU",
+                value
+            )),
+            Err(_) => Err(format!("Invalid value {}", value)),
+        },
+    }
+}
+
+// characters of a string literal once its escape sequences are replaced
+pub(crate) fn unescape(literal: &str) -> Result<Vec<char>, String> {
+    let mut characters: Vec<char> = Vec::new();
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some(c) => c,
+                None => return Err("Invalid escape sequence in string".to_string()),
+            }
+        } else {
+            c
+        };
+        characters.push(c);
+    }
+    Ok(characters)
+}
+
+// a string is stored as a zero-terminated array with one character per address
+fn translate_string(
+    name: &str,
+    literal: &str,
+    variable_names: &mut AddressNames,
+) -> Result<Vec<Instruction>, String> {
+    let mut characters: Vec<u32> = Vec::new();
+    for c in unescape(literal)? {
+        if c as u32 > 0b1111111111 {
+            return Err(format!("Character {} does not fit in 10 bits", c));
+        }
+        characters.push(c as u32);
+    }
+    let length = characters.len() as u16 + 1;
+    let variable = Variable::new(name, variable_names)?;
+    for i in 1..length {
+        variable_names.add(&(variable.name + i).to_string())?;
+    }
+    let mut instructions = vec![Instruction::ARRAY(variable, 0, length)];
+    for (i, c) in characters.iter().enumerate() {
+        let address = Variable::from(variable.name + i as u16);
+        instructions.push(Instruction::VARIABLE(address, *c as u64));
+    }
+    Ok(instructions)
+}
+
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum LineCategory {
     DATA = 0b00,
@@ -454,99 +840,177 @@ impl CoFile {
         }
     }
 
-    fn read_as_bin(&self) -> Vec<u32> {
-        let mut file: std::fs::File = std::fs::File::open(&self.filename).expect("File not found");
+    // the errors of the file system are reported before the first line
+    fn read_error(&self, e: std::io::Error) -> AssemblyError {
+        AssemblyError::at(&Location::new(0), format!("Cannot read {}: {}", self.filename, e))
+    }
+
+    fn read_as_bin(&self) -> Result<Vec<u8>, AssemblyError> {
+        let mut file: std::fs::File =
+            std::fs::File::open(&self.filename).map_err(|e| self.read_error(e))?;
         // if file is a .bin file, read it as binary
-        // in binary mode, each instruction is 32 bytes long so we need to read 32 bytes at a time
         let mut buffer: Vec<u8> = Vec::new();
-        file.read_to_end(&mut buffer).expect("Error reading file");
-        let buffer32 = buffer
-            .chunks(4)
-            .map(|chunk| {
-                if chunk.len() != 4 {
-                    panic!("Invalid file format");
-                }
-                let res: u32 = (chunk[0] as u32) << 24
-                    | (chunk[1] as u32) << 16
-                    | (chunk[2] as u32) << 8
-                    | (chunk[3] as u32);
-                // convert the 4 bytes into a u32
-                return res;
-            })
-            .collect::<Vec<u32>>();
-        return buffer32;
+        file.read_to_end(&mut buffer).map_err(|e| self.read_error(e))?;
+        Ok(buffer)
     }
 
-    pub fn read_as_text(&self) -> Vec<String> {
-        let mut file: std::fs::File = std::fs::File::open(&self.filename).expect("File not found");
-        // if file is a .bw file, read it as text
+    pub fn read_as_text(&self) -> Result<String, AssemblyError> {
+        let mut file: std::fs::File =
+            std::fs::File::open(&self.filename).map_err(|e| self.read_error(e))?;
+        // if file is a .co file, read it as text
         let mut buffer: String = String::new();
         file.read_to_string(&mut buffer)
-            .expect("Error reading file");
-        let buffer = buffer
-            .split("\n")
-            .map(|chunk| {
-                return chunk.to_string();
-            })
-            .collect::<Vec<String>>();
-        return buffer;
+            .map_err(|e| self.read_error(e))?;
+        Ok(buffer)
     }
 
-    // return a vector of u32 or String depending on the file extension
-    pub fn read(&self) -> Vec<LineType> {
+    // return a vector of u32 or String depending on the file extension,
+    // with the macros of a .co file expanded
+    pub fn read(&self) -> Result<Vec<LineType>, AssemblyError> {
+        Ok(self.read_with_locations()?.0)
+    }
+
+    // lines of the program with the place in the source each one comes from
+    fn read_with_locations(&self) -> Result<(Vec<LineType>, Vec<Location>), AssemblyError> {
         match self.extension {
             Extension::CO => {
-                let buffer = self.read_as_text();
-                let buffer = buffer
-                    .iter()
-                    .map(|line| {
-                        return LineType::String(line.to_string());
-                    })
-                    .collect::<Vec<LineType>>();
-                return buffer;
+                let path = Path::new(&self.filename);
+                let lines = preprocess(&self.read_as_text()?, Some(path))?;
+                Ok(lines
+                    .into_iter()
+                    .map(|(text, location)| (LineType::String(text), location))
+                    .unzip())
             }
             Extension::BIN => {
-                let buffer = self.read_as_bin();
-                let buffer = buffer
-                    .iter()
-                    .map(|line| {
-                        return LineType::Bin(*line);
-                    })
-                    .collect::<Vec<LineType>>();
-                return buffer;
+                let buffer = LineType::from_bytes(&self.read_as_bin()?)
+                    .map_err(|e| AssemblyError::at(&Location::new(0), e))?;
+                let locations = (1..=buffer.len()).map(Location::new).collect();
+                Ok((buffer, locations))
             }
         }
     }
 
-    pub fn export(&self) -> String {
-        let buffer = self.read();
-        let mut variable_names = AddressNames::new();
-        let mut label_names = AddressNames::new();
-        let mut res: Vec<u32> = Vec::new();
-        let mut current_category: LineCategory = LineCategory::NONE;
-        for line in buffer {
-            if line.is_category() {
-                current_category = line.get_category();
-                res.push(current_category.into());
-                continue;
+    pub fn export(&self) -> Result<String, AssemblyError> {
+        let (buffer, locations) = self.read_with_locations()?;
+        Ok(encode(buffer, locations, false)?.0)
+    }
+
+    pub fn export_optimized(&self) -> Result<(String, Vec<Change>), AssemblyError> {
+        let (buffer, locations) = self.read_with_locations()?;
+        encode(buffer, locations, true)
+    }
+}
+
+// translate the lines of a program to the binary representation of the instructions
+pub fn export(buffer: Vec<LineType>) -> Result<String, AssemblyError> {
+    let locations = (1..=buffer.len()).map(Location::new).collect();
+    Ok(encode(buffer, locations, false)?.0)
+}
+
+// same as export, with the #CODE sections rewritten by the peephole optimizer
+pub fn export_optimized(buffer: Vec<LineType>) -> Result<(String, Vec<Change>), AssemblyError> {
+    let locations = (1..=buffer.len()).map(Location::new).collect();
+    encode(buffer, locations, true)
+}
+
+// `locations` holds the place in the source of each line of the buffer
+fn encode(
+    buffer: Vec<LineType>,
+    locations: Vec<Location>,
+    optimized: bool,
+) -> Result<(String, Vec<Change>), AssemblyError> {
+    let mut variable_names = AddressNames::new();
+    let mut label_names = AddressNames::new();
+    let mut architecture = CLASSIC;
+    // words of the program, of 32 or 64 bits depending on the architecture
+    let mut res: Vec<String> = Vec::new();
+    let mut changes: Vec<Change> = Vec::new();
+    // instructions of the current #CODE section and their place, written at the end of it
+    let mut code: Vec<Instruction> = Vec::new();
+    let mut lines: Vec<Location> = Vec::new();
+    // labels defined by the program, and the ones used by the jumps with their place
+    let mut defined: HashSet<Label> = HashSet::new();
+    let mut jumps: Vec<(Label, Location)> = Vec::new();
+    let word = |architecture: &Architecture, instruction: Instruction| -> Result<String, String> {
+        architecture.check(&instruction)?;
+        architecture.check_encoding(&instruction)?;
+        if architecture.is_wide() {
+            Ok(format!("{:064b}", encode_wide(&instruction)))
+        } else {
+            Ok(format!("{:032b}", Into::<u32>::into(instruction)))
+        }
+    };
+    let mut flush = |code: &mut Vec<Instruction>,
+                     lines: &mut Vec<Location>,
+                     label_names: &AddressNames,
+                     architecture: &Architecture,
+                     res: &mut Vec<String>|
+     -> Result<(), AssemblyError> {
+        // the binary file may be run in strict mode, where `LDA Tn Tn` can fail
+        if optimized {
+            changes.extend(optimize(code, lines, label_names, architecture, true));
+        }
+        for (instruction, location) in code.drain(..).zip(lines.drain(..)) {
+            let word = word(architecture, instruction);
+            res.push(word.map_err(|e| AssemblyError::at(&location, e))?);
+        }
+        Ok(())
+    };
+    let mut current_category: LineCategory = LineCategory::NONE;
+    for (line, location) in buffer.into_iter().zip(locations) {
+        let error = |message: String| AssemblyError::at(&location, message);
+        if let Some(config) = line.config() {
+            if current_category != LineCategory::NONE {
+                return Err(error("#CONFIG must come before #DATA and #CODE".to_string()));
             }
-            if current_category == LineCategory::NONE {
-                panic!("Invalid category");
+            architecture = config.map_err(|e| error(format!("Invalid #CONFIG: {}", e)))?;
+            // the classic programs have no header
+            if architecture != CLASSIC {
+                res.push(format!("{:032b}", architecture.header()));
             }
-            if line.is_empty() || line.is_comment() {
-                continue;
+            continue;
+        }
+        if line.is_category() {
+            flush(&mut code, &mut lines, &label_names, &architecture, &mut res)?;
+            current_category = line.get_category().map_err(error)?;
+            let category: u32 = current_category.into();
+            match architecture.is_wide() {
+                true => res.push(format!("{:064b}", (category as u64) << 32)),
+                false => res.push(format!("{:032b}", category)),
             }
-            let instruction =
-                line.translate(&current_category, &mut variable_names, &mut label_names);
-            res.push(instruction.into());
+            continue;
+        }
+        if line.is_empty() || line.is_comment() {
+            continue;
+        }
+        if current_category == LineCategory::NONE {
+            return Err(error("Invalid category".to_string()));
+        }
+        let instructions = line
+            .translate_all(&current_category, &mut variable_names, &mut label_names)
+            .map_err(error)?;
+        for instruction in instructions {
+            match current_category {
+                LineCategory::CODE => {
+                    if let Instruction::LABEL(label) = instruction {
+                        defined.insert(label);
+                    }
+                    if let Some(label) = instruction.target() {
+                        jumps.push((label, location.clone()));
+                    }
+                    code.push(instruction);
+                    lines.push(location.clone());
+                }
+                _ => res.push(word(&architecture, instruction).map_err(error)?),
+            }
+        }
+    }
+    flush(&mut code, &mut lines, &label_names, &architecture, &mut res)?;
+    // every label used by a jump must be defined
+    for (label, location) in jumps {
+        if !defined.contains(&label) {
+            return Err(AssemblyError::at(&location, "Label not found".to_string()));
         }
-        let res = res
-            .iter()
-            .map(|line| {
-                return format!("{:032b}", line);
-            })
-            .collect::<Vec<String>>()
-            .join("");
-        return res;
     }
+    Ok((res.join(""), changes))
 }
@@ -0,0 +1,179 @@
+use crate::program::{AssemblyError, Program};
+use std::path::Path;
+
+// indentation of the instructions of the #CODE section
+const INDENT: &str = "    ";
+
+enum Line {
+    Blank,
+    Category(String),
+    Comment(String),
+    // name and value of a #DATA declaration or of a #CONST definition
+    Data(String, String),
+    Label(String, Option<String>),
+    // `%macro`, `%endmacro` or `.equ` with its operands, and the comment at the end of the line
+    Directive(String, Option<String>),
+    // mnemonic, operands and comment at the end of the line
+    Instruction(String, Vec<String>, Option<String>),
+}
+
+// rewrite the source of a program in the canonical layout: section headers and labels
+// in the first column, indented instructions with the operands starting on the same
+// column and the comments indented as the line they describe. The source is assembled
+// first so that only valid programs are rewritten, with the included files found
+// relative to `path`, the file of the source. The jumps may target labels defined
+// elsewhere, so that a library jumping to a label of its caller is formatted too.
+pub fn format_source(source: &str, path: Option<&Path>) -> Result<String, AssemblyError> {
+    let mut program = Program::new();
+    program.external_labels = true;
+    match path {
+        Some(path) => program.load_source_at(source, path)?,
+        None => program.load_source(source)?,
+    }
+
+    let mut data = false;
+    let mut lines: Vec<Line> = Vec::new();
+    for line in source.lines() {
+        let line = parse_line(line, data);
+        if let Line::Category(category) = &line {
+            data = category.starts_with("#DATA") || category.starts_with("#CONST");
+        }
+        lines.push(line);
+    }
+
+    // the columns are aligned within each section
+    let mut sections: Vec<Vec<&Line>> = vec![Vec::new()];
+    for line in lines.iter() {
+        if let Line::Category(_) = line {
+            sections.push(Vec::new());
+        }
+        sections.last_mut().unwrap().push(line);
+    }
+
+    let mut res: Vec<String> = Vec::new();
+    for section in sections {
+        let name_width = section
+            .iter()
+            .filter_map(|line| match line {
+                Line::Data(name, _) => Some(name.len()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        let mnemonic_width = section
+            .iter()
+            .filter_map(|line| match line {
+                Line::Instruction(mnemonic, _, _) => Some(mnemonic.len()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        let text: Vec<String> = section
+            .iter()
+            .map(|line| match line {
+                Line::Instruction(mnemonic, operands, _) => {
+                    let text = format!(
+                        "{}{:<3$} {}",
+                        INDENT,
+                        mnemonic,
+                        operands.join(" "),
+                        mnemonic_width
+                    );
+                    text.trim_end().to_string()
+                }
+                Line::Label(name, _) => format!("{}:", name),
+                Line::Directive(directive, _) => directive.clone(),
+                Line::Data(name, value) => format!("{:<2$} {}", name, value, name_width),
+                _ => String::new(),
+            })
+            .collect();
+        // comments at the end of the lines start on the same column
+        let comment_column = section
+            .iter()
+            .zip(text.iter())
+            .filter_map(|(line, text)| match line {
+                Line::Instruction(_, _, Some(_))
+                | Line::Label(_, Some(_))
+                | Line::Directive(_, Some(_)) => Some(text.len()),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        for (i, (line, text)) in section.iter().zip(text).enumerate() {
+            match line {
+                Line::Blank => {
+                    // keep a single blank line between two groups of lines
+                    if res.last().is_some_and(|l| !l.is_empty() && !l.starts_with('#')) {
+                        res.push(String::new());
+                    }
+                }
+                Line::Category(category) => {
+                    while res.last().is_some_and(|l| l.is_empty()) {
+                        res.pop();
+                    }
+                    if !res.is_empty() {
+                        res.push(String::new());
+                    }
+                    res.push(category.clone());
+                }
+                Line::Comment(comment) => {
+                    // a comment is indented as the next line that is not a comment
+                    let indented = section[i..]
+                        .iter()
+                        .find(|l| !matches!(l, Line::Comment(_) | Line::Blank))
+                        .is_some_and(|l| matches!(l, Line::Instruction(_, _, _)));
+                    let indent = if indented { INDENT } else { "" };
+                    res.push(format!("{}{}", indent, comment));
+                }
+                Line::Instruction(_, _, Some(comment))
+                | Line::Label(_, Some(comment))
+                | Line::Directive(_, Some(comment)) => {
+                    res.push(format!("{:<2$} {}", text, comment, comment_column));
+                }
+                _ => res.push(text),
+            }
+        }
+    }
+    while res.last().is_some_and(|l| l.is_empty()) {
+        res.pop();
+    }
+    let mut formatted = res.join("\n");
+    formatted.push('\n');
+    Ok(formatted)
+}
+
+fn parse_line(line: &str, data: bool) -> Line {
+    let line = line.trim();
+    if line.is_empty() {
+        return Line::Blank;
+    }
+    if line.starts_with('#') {
+        return Line::Category(line.to_string());
+    }
+    if line.starts_with(';') {
+        return Line::Comment(line.to_string());
+    }
+    if line.starts_with('%') || line.split_whitespace().next() == Some(".equ") {
+        let (code, comment) = match line.split_once(';') {
+            Some((code, comment)) => (code, Some(format!(";{}", comment))),
+            None => (line, None),
+        };
+        let words: Vec<&str> = code.split_whitespace().collect();
+        return Line::Directive(words.join(" "), comment);
+    }
+    if data {
+        // the value is kept as written, it can be a string containing spaces
+        let (name, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        return Line::Data(name.to_string(), value.trim().to_string());
+    }
+    let (code, comment) = match line.split_once(';') {
+        Some((code, comment)) => (code.trim(), Some(format!(";{}", comment))),
+        None => (line, None),
+    };
+    if let Some(name) = code.strip_suffix(':') {
+        return Line::Label(name.to_string(), comment);
+    }
+    let mut words = code.split_whitespace().map(|w| w.to_string());
+    let mnemonic = words.next().unwrap();
+    Line::Instruction(mnemonic, words.collect(), comment)
+}
@@ -0,0 +1,159 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+// source of the values read by IN and destination of the text written by OUT
+pub trait IoHandler {
+    // next input value, None when there is no valid input left
+    fn read(&mut self) -> Option<u64>;
+    fn write(&mut self, text: &str);
+
+    // next input character, by default the next value read as a character code
+    fn read_char(&mut self) -> Option<char> {
+        self.read()
+            .and_then(|value| u32::try_from(value).ok())
+            .and_then(char::from_u32)
+    }
+}
+
+// read the values from stdin, one per line, and write to stdout
+#[derive(Default)]
+pub struct StdIo {
+    // source of the lines instead of stdin
+    reader: Option<Box<dyn BufRead>>,
+    // characters of the current line not read yet by GETC
+    pending: VecDeque<char>,
+}
+
+impl StdIo {
+    // read the lines from another source than stdin
+    pub fn from_reader(reader: impl BufRead + 'static) -> StdIo {
+        StdIo {
+            reader: Some(Box::new(reader)),
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut input = String::new();
+        match &mut self.reader {
+            Some(reader) => reader.read_line(&mut input).ok()?,
+            None => std::io::stdin().read_line(&mut input).ok()?,
+        };
+        Some(input)
+    }
+}
+
+impl IoHandler for StdIo {
+    fn read(&mut self) -> Option<u64> {
+        let mut input: String = self.pending.drain(..).collect();
+        // the end of a line left by GETC does not hold a value
+        if input.trim().is_empty() {
+            input = self.read_line()?;
+        }
+        input.trim().parse().ok()
+    }
+
+    fn read_char(&mut self) -> Option<char> {
+        if self.pending.is_empty() {
+            let input = self.read_line()?;
+            self.pending.extend(input.chars());
+        }
+        self.pending.pop_front()
+    }
+
+    fn write(&mut self, text: &str) {
+        print!("{}", text);
+        std::io::stdout().flush().unwrap();
+    }
+}
+
+// in-memory input and output, the clones share the same buffers so the
+// output can still be read after the handler has been given to a program
+#[derive(Clone, Default)]
+pub struct QueueIo {
+    input: Rc<RefCell<VecDeque<u64>>>,
+    output: Rc<RefCell<String>>,
+}
+
+impl QueueIo {
+    pub fn new(input: &[u64]) -> QueueIo {
+        QueueIo {
+            input: Rc::new(RefCell::new(input.iter().copied().collect())),
+            output: Rc::new(RefCell::new(String::new())),
+        }
+    }
+
+    pub fn push_input(&self, value: u64) {
+        self.input.borrow_mut().push_back(value);
+    }
+
+    // add the codes of the characters of a text to the input
+    pub fn push_str(&self, text: &str) {
+        self.input
+            .borrow_mut()
+            .extend(text.chars().map(|c| c as u64));
+    }
+
+    // everything written by the program so far
+    pub fn output(&self) -> String {
+        self.output.borrow().clone()
+    }
+}
+
+impl IoHandler for QueueIo {
+    fn read(&mut self) -> Option<u64> {
+        self.input.borrow_mut().pop_front()
+    }
+
+    fn write(&mut self, text: &str) {
+        self.output.borrow_mut().push_str(text);
+    }
+}
+
+// read the values from a script given in advance and write to stdout
+pub struct ScriptedIo {
+    inputs: VecDeque<u64>,
+    // print each value read to stderr, as if it had been typed
+    pub echo: bool,
+}
+
+impl ScriptedIo {
+    pub fn new(inputs: Vec<u64>) -> ScriptedIo {
+        ScriptedIo {
+            inputs: inputs.into(),
+            echo: false,
+        }
+    }
+
+    // parse a script of values separated by commas, spaces or new lines
+    pub fn parse(script: &str) -> Result<ScriptedIo, String> {
+        let mut inputs: Vec<u64> = Vec::new();
+        for value in script
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|v| !v.is_empty())
+        {
+            match value.parse::<u64>() {
+                Ok(value) => inputs.push(value),
+                Err(_) => return Err(format!("invalid input value {}", value)),
+            }
+        }
+        Ok(ScriptedIo::new(inputs))
+    }
+}
+
+impl IoHandler for ScriptedIo {
+    fn read(&mut self) -> Option<u64> {
+        let value = self.inputs.pop_front()?;
+        if self.echo {
+            eprintln!("> {}", value);
+        }
+        Some(value)
+    }
+
+    fn write(&mut self, text: &str) {
+        print!("{}", text);
+        std::io::stdout().flush().unwrap();
+    }
+}
@@ -1,3 +1,18 @@
+pub mod arch;
+pub mod cache;
+pub mod cfg;
+pub mod constants;
+pub mod disasm;
 pub mod enums;
 pub mod file;
+pub mod format;
+pub mod io;
+pub mod listing;
+pub mod lint;
+pub mod optimize;
+pub mod pipeline;
+pub mod preprocess;
 pub mod program;
+pub mod pseudo;
+pub mod stack;
+pub mod timing;
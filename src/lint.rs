@@ -0,0 +1,308 @@
+use crate::cfg::successors;
+use crate::enums::{Instruction, Register};
+use crate::file::unescape;
+use crate::preprocess::{preprocess, Location};
+use crate::program::{AssemblyError, Program};
+use crate::stack::analyze;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+// mistake found in a program that assembles
+#[derive(Debug, PartialEq)]
+pub struct Warning {
+    pub location: Location,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+// variable declared in the #DATA section
+struct Declaration {
+    name: String,
+    length: u16,
+    location: Location,
+}
+
+// check the source of a program for common mistakes, the warnings are sorted by place.
+// The included files are found relative to `path`, the file of the source.
+pub fn lint(source: &str, path: Option<&Path>) -> Result<Vec<Warning>, AssemblyError> {
+    let mut program = Program::new();
+    match path {
+        Some(path) => program.load_source_at(source, path)?,
+        None => program.load_source(source)?,
+    }
+    let mut warnings: Vec<Warning> = Vec::new();
+    let lines = preprocess(source, path)?;
+    let declarations = check_data(&lines, &program, &mut warnings);
+    check_operands(&program, &declarations, &mut warnings);
+    check_labels(&program, &mut warnings);
+    check_flow(&program, &mut warnings);
+    check_stack(&program, &mut warnings);
+    // the local and numeric labels are named as in the source
+    for warning in warnings.iter_mut() {
+        warning.message = warning.location.original(&warning.message);
+    }
+    warnings.sort_by_key(|w| (w.location.file.clone(), w.location.line));
+    Ok(warnings)
+}
+
+// the declarations are read from the lines of the source, with the included files and
+// the constants, since the values are truncated when assembled
+fn check_data(
+    lines: &[(String, Location)],
+    program: &Program,
+    warnings: &mut Vec<Warning>,
+) -> Vec<Declaration> {
+    let mut declarations: Vec<Declaration> = Vec::new();
+    let mut data = false;
+    for (line, location) in lines {
+        let line = line.trim();
+        if line.starts_with('#') {
+            data = line.starts_with("#DATA");
+            continue;
+        }
+        if !data || line.is_empty() || line.starts_with(';') || line.starts_with(".equ") {
+            continue;
+        }
+        let (name, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let value = value.trim();
+        let (name, length) = match name.split_once('[') {
+            Some((name, length)) => match length.trim_end_matches(']').parse() {
+                Ok(length) => (name, length),
+                // a length given by a constant expression is read from the program
+                Err(_) => (name, array_length(program, name)),
+            },
+            None if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') => {
+                // the characters of the string and the terminating zero
+                let literal = &value[1..value.len() - 1];
+                (name, unescape(literal).unwrap_or_default().len() as u16 + 1)
+            }
+            None => (name, 1),
+        };
+        // largest value that fits in the bits of a #DATA value
+        let max_value = program.architecture.max_value() as i64;
+        if let Ok(value) = value.parse::<i64>() {
+            if !(0..=max_value).contains(&value) {
+                let bits = program.architecture.value_bits();
+                // a 64-bit machine keeps the whole value, which a binary program cannot hold
+                let message = match program.architecture.word {
+                    64 => format!(
+                        "value {} of {} does not fit in {} bits and cannot be exported",
+                        value, name, bits
+                    ),
+                    _ => format!(
+                        "value {} of {} does not fit in {} bits and is stored as {}",
+                        value,
+                        name,
+                        bits,
+                        value & max_value
+                    ),
+                };
+                warnings.push(Warning {
+                    location: location.clone(),
+                    message,
+                });
+            }
+        }
+        declarations.push(Declaration {
+            name: name.to_string(),
+            length,
+            location: location.clone(),
+        });
+    }
+    declarations
+}
+
+// the addresses of an array after the first one are named by their number
+fn array_length(program: &Program, name: &str) -> u16 {
+    let start = match program.variable_names.position(name) {
+        Some(start) => start,
+        None => return 1,
+    };
+    let names = &program.variable_names;
+    (start + 1..)
+        .take_while(|a| names.name(*a) == Some(a.to_string().as_str()))
+        .count() as u16
+        + 1
+}
+
+fn check_operands(program: &Program, declarations: &[Declaration], warnings: &mut Vec<Warning>) {
+    let mut used: HashSet<u16> = HashSet::new();
+    for (index, instruction) in program.instructions.iter().enumerate() {
+        let location = &program.locations[index];
+        for variable in instruction.variables() {
+            used.insert(variable.name);
+            // an address with an offset is written `A+i` or `A[i]` and named `A+i`
+            let (name, offset) = match variable.alias.and_then(|a| a.split_once('+')) {
+                Some((name, offset)) => (name, offset.parse::<u16>().unwrap_or(0)),
+                None => continue,
+            };
+            if let Some(declaration) = declarations.iter().find(|d| d.name == name) {
+                if offset >= declaration.length {
+                    warnings.push(Warning {
+                        location: location.clone(),
+                        message: format!(
+                            "{}[{}] is outside of {}, which has {} element(s)",
+                            name, offset, name, declaration.length
+                        ),
+                    });
+                }
+            }
+        }
+    }
+    for declaration in declarations {
+        let start = match program.variable_names.position(&declaration.name) {
+            Some(start) => start,
+            None => continue,
+        };
+        if !(start..start + declaration.length).any(|address| used.contains(&address)) {
+            warnings.push(Warning {
+                location: declaration.location.clone(),
+                message: format!("variable {} is never used", declaration.name),
+            });
+        }
+    }
+}
+
+fn check_labels(program: &Program, warnings: &mut Vec<Warning>) {
+    let targets: HashSet<_> = program
+        .instructions
+        .iter()
+        .filter_map(|i| i.target())
+        .collect();
+    for (index, instruction) in program.instructions.iter().enumerate() {
+        if let Instruction::LABEL(label) = instruction {
+            if !targets.contains(label) {
+                let name = program.label_names.name(label.name).unwrap_or_default();
+                warnings.push(Warning {
+                    location: program.locations[index].clone(),
+                    message: format!("label {} is never jumped to", name),
+                });
+            }
+        }
+    }
+}
+
+// walk the paths of the program to find the unreachable code, the registers read before
+// being written, the end of the program without HLT and the unbalanced PUSH
+fn check_flow(program: &Program, warnings: &mut Vec<Warning>) {
+    let count = program.instructions.len();
+    if count == 0 {
+        return;
+    }
+    let bit = |r: &Register| 1u16 << *r as u16;
+    // registers written on every path leading to each instruction
+    let mut written: Vec<Option<u16>> = vec![None; count + 1];
+    // depth of the stack before each instruction, the first depth found is kept
+    let mut depths: Vec<Option<i64>> = vec![None; count + 1];
+    let mut conflicts: HashSet<usize> = HashSet::new();
+    written[0] = Some(0);
+    depths[0] = Some(0);
+    let mut pending: Vec<usize> = vec![0];
+    while let Some(index) = pending.pop() {
+        if index == count {
+            continue;
+        }
+        let instruction = &program.instructions[index];
+        let mut registers_out = written[index].unwrap();
+        if let Some(register) = instruction.register_written() {
+            registers_out |= bit(&register);
+        }
+        let depth = depths[index].unwrap();
+        let depth_out = match instruction {
+            Instruction::PUSH(_) => depth + 1,
+            Instruction::POP(_) => (depth - 1).max(0),
+            _ => depth,
+        };
+        for next in successors(program, index) {
+            let mut changed = false;
+            match written[next] {
+                Some(registers_in) if registers_in & registers_out == registers_in => {}
+                Some(registers_in) => {
+                    written[next] = Some(registers_in & registers_out);
+                    changed = true;
+                }
+                None => {
+                    written[next] = Some(registers_out);
+                    changed = true;
+                }
+            }
+            match depths[next] {
+                Some(d) if d != depth_out => {
+                    if next < count && conflicts.insert(next) {
+                        warnings.push(Warning {
+                            location: program.locations[next].clone(),
+                            message: format!(
+                                "the stack holds {} or {} value(s) depending on the path",
+                                d.min(depth_out),
+                                d.max(depth_out)
+                            ),
+                        });
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    depths[next] = Some(depth_out);
+                    changed = true;
+                }
+            }
+            if changed {
+                pending.push(next);
+            }
+        }
+    }
+
+    let mut unreachable = false;
+    for (index, instruction) in program.instructions.iter().enumerate() {
+        let location = &program.locations[index];
+        let (registers_in, depth) = match (written[index], depths[index]) {
+            (Some(registers_in), Some(depth)) => (registers_in, depth),
+            _ => {
+                // only the first instruction of unreachable code is reported
+                if !unreachable && !matches!(instruction, Instruction::LABEL(_)) {
+                    warnings.push(Warning {
+                        location: location.clone(),
+                        message: "unreachable code".to_string(),
+                    });
+                    unreachable = true;
+                }
+                continue;
+            }
+        };
+        unreachable = false;
+        let mut read: Vec<Register> = instruction.registers_read();
+        read.dedup();
+        for register in read {
+            if registers_in & bit(&register) == 0 {
+                warnings.push(Warning {
+                    location: location.clone(),
+                    message: format!("{} can be read before it is written", register),
+                });
+            }
+        }
+        if matches!(instruction, Instruction::HLT(_)) && depth > 0 && !conflicts.contains(&index) {
+            warnings.push(Warning {
+                location: location.clone(),
+                message: format!("{} value(s) left on the stack at HLT", depth),
+            });
+        }
+    }
+    if written[count].is_some() {
+        warnings.push(Warning {
+            location: program.locations[count - 1].clone(),
+            message: "the program can reach its end without HLT".to_string(),
+        });
+    }
+}
+
+// POP on an empty stack and loops growing the stack, found by the stack analysis
+fn check_stack(program: &Program, warnings: &mut Vec<Warning>) {
+    for (location, message) in analyze(program).warnings(program) {
+        warnings.push(Warning { location, message });
+    }
+}
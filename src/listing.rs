@@ -0,0 +1,54 @@
+use crate::preprocess::{preprocess, Location};
+use crate::program::{AssemblyError, Program};
+use std::path::Path;
+
+// each line of the #CODE section next to the instructions it is assembled to, showing
+// the expansion of the pseudo-instructions. The lines are shown once their macros and
+// constants are replaced, with the local and numeric labels spelled as in the source and
+// the included files found relative to `path`.
+pub fn listing(source: &str, path: Option<&Path>) -> Result<String, AssemblyError> {
+    let mut program = Program::new();
+    match path {
+        Some(path) => program.load_source_at(source, path)?,
+        None => program.load_source(source)?,
+    }
+    let lines = preprocess(source, path)?;
+
+    // the instructions assembled from a line share its place
+    let mut groups: Vec<(&Location, Vec<usize>)> = Vec::new();
+    for (index, location) in program.locations.iter().enumerate() {
+        match groups.last_mut() {
+            Some((last, indices)) if *last == location => indices.push(index),
+            _ => groups.push((location, vec![index])),
+        }
+    }
+    let text = |location: &Location| match lines.iter().find(|(_, l)| l == location) {
+        Some((text, _)) => location.original(text.split(';').next().unwrap().trim()),
+        None => String::new(),
+    };
+    let width = groups
+        .iter()
+        .map(|(location, _)| text(location).len())
+        .chain(["Source".len()])
+        .max()
+        .unwrap();
+
+    let mut listing = format!(
+        "{:>5}  {:<3$}  {}\n",
+        "Line", "Source", "Instructions", width
+    );
+    for (location, indices) in groups {
+        let source = text(location);
+        for (i, index) in indices.into_iter().enumerate() {
+            let (line, source) = match i {
+                0 => (location.line.to_string(), source.as_str()),
+                _ => (String::new(), ""),
+            };
+            let instruction = location.original(&program.instruction_text(index));
+            let row = format!("{:>5}  {:<3$}  {}", line, source, instruction, width);
+            listing.push_str(row.trim_end());
+            listing.push('\n');
+        }
+    }
+    Ok(listing)
+}
@@ -1,141 +1,600 @@
-use std::fs::File;
-use std::io::Write;
-use std::str::FromStr;
-mod enums;
-mod file;
-mod program;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use copper::arch::Architecture;
+use copper::cache::CacheConfig;
+use copper::cfg::{profile, Cfg};
+use copper::disasm::disassemble;
+use copper::enums::Extension;
+use copper::file::CoFile;
+use copper::format::format_source;
+use copper::io::ScriptedIo;
+use copper::listing::listing;
+use copper::pipeline::Pipeline;
+use copper::preprocess::Location;
+use copper::program::{Program, ProgramBuilder, RunOutcome};
+use copper::stack::analyze;
+use copper::timing::CostModel;
+use std::path::{Path, PathBuf};
 
+#[derive(Parser)]
+#[command(
+    name = "copper",
+    version,
+    about = "Assembler and interpreter for the Copper language",
+    after_help = "Exit codes: 0 success, 1 usage error, 2 assembly error, 3 runtime error, \
+4 step limit, 5 check failed, 10 + n for HLT n (255 when n is 245 or more)"
+)]
+struct Cli {
+    /// Verbose mode
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
 enum Command {
-    Run,
-    Export,
+    /// Run a program, the default when no command is given
+    Run(RunArgs),
+    /// Run a program one instruction at a time, printing the registers and the memory
+    Debug(ProgramArgs),
+    /// Assemble a program and report the first error without running it
+    Check {
+        /// Program to check (.co or .bin)
+        file: PathBuf,
+        /// Machine of a program without #CONFIG (classic, wide, small, classic64, key=value...)
+        #[arg(long, value_name = "CONFIG")]
+        arch: Option<Architecture>,
+    },
+    /// Assemble a program to a binary file
+    Export {
+        /// Program to assemble (.co)
+        file: PathBuf,
+        /// Binary file to write, the program with the .bin extension by default
+        output: Option<PathBuf>,
+        /// Rewrite the code with the peephole optimizer and report the changes
+        #[arg(long)]
+        optimize: bool,
+    },
+    /// Write the control-flow graph of a program in the DOT language of Graphviz
+    Cfg {
+        #[command(flatten)]
+        program: ProgramArgs,
+        /// DOT file to write, the program with the .dot extension by default
+        output: Option<PathBuf>,
+        /// Run the program and annotate the graph with the execution counts
+        #[arg(long)]
+        counts: bool,
+    },
+    /// Print the depth of the stack before each instruction and its problems
+    Stack {
+        /// Program to analyze (.co or .bin)
+        file: PathBuf,
+        /// Machine of a program without #CONFIG (classic, wide, small, classic64, key=value...)
+        #[arg(long, value_name = "CONFIG")]
+        arch: Option<Architecture>,
+    },
+    /// Print each line of the code of a program with the instructions it is assembled to
+    List {
+        /// Program to list (.co)
+        file: PathBuf,
+    },
+    /// Report common mistakes in programs
+    Lint {
+        /// Programs to check (.co)
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Rewrite programs in the canonical layout
+    Fmt {
+        /// Programs to format (.co)
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Only report the programs that are not formatted, without rewriting them
+        #[arg(long)]
+        check: bool,
+    },
+    /// Translate a program back to its source
+    Disasm {
+        /// Program to translate (.bin or .co)
+        file: PathBuf,
+        /// Source file to write, stdout by default
+        output: Option<PathBuf>,
+    },
+}
+
+// options shared by the commands executing a program
+#[derive(Args)]
+struct ProgramArgs {
+    /// Program to execute (.co or .bin)
+    file: PathBuf,
+    /// Stop the program after n executed instructions
+    #[arg(long, value_name = "N")]
+    max_steps: Option<u64>,
+    /// Read the values of IN from a comma separated list
+    #[arg(long, value_name = "VALUES", conflicts_with = "input_file")]
+    input: Option<String>,
+    /// Read the values of IN from a file
+    #[arg(long, value_name = "FILE")]
+    input_file: Option<PathBuf>,
+    /// Override the value of a #DATA variable, can be repeated
+    #[arg(long, value_name = "NAME=VALUE")]
+    set: Vec<String>,
+    /// Stop on reading a register or a memory cell that was never written
+    #[arg(long)]
+    strict: bool,
+    /// Rewrite the code with the peephole optimizer before running it
+    #[arg(long)]
+    optimize: bool,
+    /// Machine of a program without #CONFIG (classic, wide, small, classic64, key=value...)
+    #[arg(long, value_name = "CONFIG")]
+    arch: Option<Architecture>,
 }
 
-impl FromStr for Command {
-    type Err = ();
+#[derive(Args)]
+struct RunArgs {
+    #[command(flatten)]
+    program: ProgramArgs,
+    /// Only print the output of the program (default), even with --verbose
+    #[arg(short, long, conflicts_with = "stats")]
+    quiet: bool,
+    /// Print the times, the cycle counts and the cache counters
+    #[arg(short, long)]
+    stats: bool,
+    /// Load the cycle cost table from a file
+    #[arg(short, long, value_name = "FILE")]
+    cost_model: Option<PathBuf>,
+    /// Simulate a 5-stage pipeline and print its diagram
+    #[arg(short, long)]
+    pipeline: bool,
+    /// Enable forwarding in the pipeline
+    #[arg(short, long, requires = "pipeline")]
+    forwarding: bool,
+    /// Simulate a data cache (size=64,line=4,ways=1,policy=lru,write=back)
+    #[arg(long, value_name = "OPTIONS")]
+    cache: Option<CacheConfig>,
+}
+
+// exit codes of the interpreter, HLT <code> exits with EXIT_HALT + code
+const EXIT_USAGE: i32 = 1;
+const EXIT_ASSEMBLY_ERROR: i32 = 2;
+const EXIT_RUNTIME_TRAP: i32 = 3;
+const EXIT_STEP_LIMIT: i32 = 4;
+// `fmt --check` found a program that is not formatted or `lint` found a mistake
+const EXIT_CHECK_FAILED: i32 = 5;
+// first exit code of HLT <code>, above the codes of the interpreter, the codes that do
+// not fit in the 8 bits of an exit status exit with 255
+const EXIT_HALT: i32 = 10;
+
+// first arguments that are not a program to run, besides the commands
+const HELP_ARGUMENTS: [&str; 5] = ["help", "-h", "--help", "-V", "--version"];
 
-    fn from_str(input: &str) -> Result<Command, Self::Err> {
-        match input {
-            "run" => Ok(Command::Run),
-            "export" => Ok(Command::Export),
-            _ => Err(()),
+// open a program, the error is the exit code when the file does not exist or is not a program
+fn open(path: &Path, verbose: bool) -> Result<CoFile, i32> {
+    if !path.exists() {
+        eprintln!("File {} does not exist", path.display());
+        return Err(EXIT_USAGE);
+    }
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("co") | Some("bin") => {}
+        _ => {
+            eprintln!("File {} is not a .co or .bin program", path.display());
+            return Err(EXIT_USAGE);
         }
     }
+    let file = CoFile::new(path.to_string_lossy().to_string());
+    if verbose {
+        eprintln!("File: {}", file.filename);
+        eprintln!("Extension: {}", file.extension);
+    }
+    Ok(file)
 }
 
-const VERSION: &str = env!("CARGO_PKG_VERSION");
-const HELP_MESSAGE: &str = "Usage: copper <filename>\n\nOptions:\n\t-h, --help\t\tPrint this help message\n\t-V, --version\t\tPrint version information\n\t-v, --verbose\t\tVerbose mode\n\t-d, --debug\t\tDebug mode\n\nCommands:\n\trun <filename>\t\t\t\tRun the program\n\texport <filename> [<outputfile>]\tExport the program to a binary file\n\nExamples:\n\tcopper program.co\n\tcopper run program.co\n\tcopper export program.co\n\tcopper export program.co program.bin\n";
+// `file:line` of a place in a program or in one of the files it includes
+fn place(path: &Path, location: &Location) -> String {
+    match &location.file {
+        Some(file) => format!("{}:{}", file, location.line),
+        None => format!("{}:{}", path.display(), location.line),
+    }
+}
 
-fn main() {
-    // get the name of the file from the command line
-    let mut args: Vec<String> = std::env::args().collect();
-    // remove the first argument (the name of the program)
-    args.remove(0);
-    let mut command = Command::Run;
-    let command_str = if args.len() > 0 { &args[0] } else { "" };
-    match Command::from_str(&command_str) {
-        Ok(c) => {
-            command = c;
-            args.remove(0);
-        }
-        Err(_) => {}
-    }
-    // check if there is some parameter argument
-    for arg in args.iter() {
-        if arg == "-h" || arg == "--help" {
-            println!("{}", HELP_MESSAGE);
-            std::process::exit(0);
-        }
-        if arg == "-V" || arg == "--version" {
-            println!("Version: {}", VERSION);
-            std::process::exit(0);
-        }
-        if arg == "-v" || arg == "--verbose" {
-            println!("Verbose mode");
-            std::env::set_var("RUST_LOG", "verbose");
-        }
-        if arg == "-d" || arg == "--debug" {
-            println!("Debug mode");
-            std::env::set_var("DEBUG_MODE", "debug");
-        }
-    }
-    // get the filename (argument without a dash)
-    args.reverse();
-    let filename: String = loop {
-        match args.pop() {
-            Some(arg) => {
-                if !arg.starts_with("-") {
-                    println!("Filename: {}", arg);
-                    break arg;
-                }
+// text of a .co program
+fn read_source(path: &Path, verbose: bool) -> Result<String, i32> {
+    let file = open(path, verbose)?;
+    if file.extension != Extension::CO {
+        eprintln!("File {} is not a .co program", path.display());
+        return Err(EXIT_USAGE);
+    }
+    file.read_as_text().map_err(|e| {
+        eprintln!("{}: {}", path.display(), e.message);
+        EXIT_USAGE
+    })
+}
+
+// empty program for the machine given with --arch, the classic one by default
+fn program_for(arch: Option<Architecture>) -> Program {
+    let mut builder = ProgramBuilder::new();
+    if let Some(architecture) = arch {
+        builder = builder.architecture(architecture);
+    }
+    builder.build()
+}
+
+// build and load a program with the options shared by run and debug
+fn load(mut builder: ProgramBuilder, args: &ProgramArgs, verbose: bool) -> Result<Program, i32> {
+    builder = builder
+        .verbose(verbose)
+        .strict(args.strict)
+        .optimize(args.optimize);
+    if let Some(architecture) = args.arch {
+        builder = builder.architecture(architecture);
+    }
+    if let Some(steps) = args.max_steps {
+        builder = builder.max_steps(steps);
+    }
+    let script = match (&args.input, &args.input_file) {
+        (Some(values), _) => Some(values.clone()),
+        (None, Some(path)) => match std::fs::read_to_string(path) {
+            Ok(script) => Some(script),
+            Err(e) => {
+                eprintln!("Cannot read {}: {}", path.display(), e);
+                return Err(EXIT_USAGE);
+            }
+        },
+        (None, None) => None,
+    };
+    if let Some(script) = script {
+        match ScriptedIo::parse(&script) {
+            Ok(mut io) => {
+                io.echo = verbose;
+                builder = builder.io(Box::new(io));
             }
-            None => {
-                println!("No filename given. Use -h or --help for help.");
-                std::process::exit(1);
+            Err(e) => {
+                eprintln!("Invalid input: {}", e);
+                return Err(EXIT_USAGE);
             }
         }
-    };
-    let filename = filename.as_str();
-
-    let mut program = program::Program::new();
-
-    // check if the file exists
-    if !std::path::Path::new(filename).exists() {
-        println!("File {} does not exist", filename);
-        std::process::exit(1);
-    }
-    // create a new CoFile
-    let file = file::CoFile::new(filename.to_string());
-
-    if program.verbose {
-        println!("File: {}", file.filename);
-        println!("Extension: {}", file.extension);
-    }
-
-    match command {
-        Command::Export => {
-            let t3 = std::time::Instant::now();
-            let data = file.export();
-            println!("Exported data: {}", data);
-            let name: String = if args.len() > 0 {
-                let name = &args[0];
-                if name.ends_with(".bin") {
-                    name.replace(".bin", "")
-                } else {
-                    println!("Invalid filename. Use -h or --help for help.");
-                    std::process::exit(1);
-                }
-            } else {
-                file.filename.clone().replace(".co", "")
+    }
+    let mut program = builder.build();
+    let file = open(&args.file, verbose)?;
+    match program.load(file) {
+        Ok(()) => {}
+        Err(e) => {
+            eprintln!("Assembly error at {}", e);
+            return Err(EXIT_ASSEMBLY_ERROR);
+        }
+    }
+    if verbose {
+        for change in program.optimizations.iter() {
+            eprintln!(
+                "{}: {}",
+                place(&args.file, &change.location),
+                change.message
+            );
+        }
+    }
+    for assignment in args.set.iter() {
+        let result = match assignment.split_once('=') {
+            Some((name, value)) => match value.parse::<u64>() {
+                Ok(value) => program.set_variable_by_name(name, value),
+                Err(_) => Err(format!("invalid value {}", value)),
+            },
+            None => Err("expected <name>=<value>".to_string()),
+        };
+        if let Err(e) = result {
+            eprintln!("Invalid --set {}: {}", assignment, e);
+            return Err(EXIT_USAGE);
+        }
+    }
+    Ok(program)
+}
+
+// exit code of the interpreter for the outcome of a program
+fn exit_code(program: &Program, outcome: RunOutcome) -> i32 {
+    match outcome {
+        RunOutcome::Halted(0) => 0,
+        RunOutcome::Halted(code) if code < (255 - EXIT_HALT) as u32 => EXIT_HALT + code as i32,
+        RunOutcome::Halted(code) => {
+            eprintln!("Program halted with code {}", code);
+            255
+        }
+        RunOutcome::Finished => 0,
+        RunOutcome::StepLimit => {
+            eprintln!("Step limit exceeded");
+            EXIT_STEP_LIMIT
+        }
+        RunOutcome::Trap { index, message } => {
+            let location = match program.locations.get(index) {
+                Some(location) => location.to_string(),
+                None => format!("instruction {}", index),
             };
-            // create a new file with the same name but with the extension .bin and write the exported file to it
-            let mut file = File::create(format!("{}.bin", name)).expect("creation failed");
-            // cut the string into packages of 8 bits
-            let bytes: Vec<u8> = data
-                .chars()
-                .collect::<Vec<char>>()
-                .chunks(8)
-                .map(|chunk| {
-                    let s: String = chunk.iter().collect();
-                    u8::from_str_radix(&s, 2).unwrap()
-                })
-                .collect();
-            // write the bytes to the file
-            file.write_all(&bytes).expect("write failed");
-            println!("Time to export: {:?}", t3.elapsed());
-            std::process::exit(0);
-        }
-        Command::Run => {
-            let t1 = std::time::Instant::now();
-            program.load(file);
-            println!("Time to load: {:?}", t1.elapsed());
-            let t2 = std::time::Instant::now();
-            if std::env::var("DEBUG_MODE").is_ok() {
-                program.run_debug();
-            } else {
-                program.run();
+            eprintln!(
+                "Runtime error at {} ({}): {}",
+                location, program.instructions[index], message
+            );
+            EXIT_RUNTIME_TRAP
+        }
+    }
+}
+
+fn run(args: RunArgs, verbose: bool) -> Result<i32, i32> {
+    let verbose = verbose && !args.quiet;
+    let mut builder = ProgramBuilder::new();
+    if let Some(path) = &args.cost_model {
+        let model = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|table| table.parse::<CostModel>());
+        match model {
+            Ok(model) => builder = builder.cost_model(model),
+            Err(e) => {
+                eprintln!("Invalid cost model {}: {}", path.display(), e);
+                return Err(EXIT_USAGE);
+            }
+        }
+    }
+    if let Some(config) = args.cache.clone() {
+        builder = builder.cache(config);
+    }
+    let t1 = std::time::Instant::now();
+    let mut program = load(builder, &args.program, verbose)?;
+    let load_time = t1.elapsed();
+    let t2 = std::time::Instant::now();
+    let outcome = if args.pipeline {
+        let mut pipeline = Pipeline::new(args.forwarding);
+        let outcome = pipeline.run(&mut program);
+        eprint!("{}", pipeline.stats);
+        outcome
+    } else {
+        program.run()
+    };
+    let run_time = t2.elapsed();
+    // the counters are only printed on request so that stdout only holds the output of the program
+    if args.stats {
+        eprintln!("Time to load: {:?}", load_time);
+        eprintln!("Time to run: {:?}", run_time);
+        if !args.pipeline {
+            eprint!("{}", program.stats);
+        }
+        if let Some(cache) = &program.cache {
+            let cache = cache.borrow();
+            eprint!("{}", cache.stats);
+            eprint!("{}", cache.report(&program.instructions));
+        }
+    }
+    let code = exit_code(&program, outcome);
+    if verbose {
+        eprintln!("Program finished");
+    }
+    Ok(code)
+}
+
+fn debug(args: ProgramArgs, verbose: bool) -> Result<i32, i32> {
+    let mut program = load(ProgramBuilder::new(), &args, verbose)?;
+    let outcome = program.run_debug();
+    Ok(exit_code(&program, outcome))
+}
+
+fn check(path: &Path, arch: Option<Architecture>, verbose: bool) -> Result<i32, i32> {
+    let file = open(path, verbose)?;
+    let mut program = program_for(arch);
+    match program.load(file) {
+        Ok(()) => {
+            if verbose {
+                eprintln!(
+                    "{}: {} instructions",
+                    path.display(),
+                    program.instructions.len()
+                );
             }
-            println!("Time to run: {:?}", t2.elapsed());
+            Ok(0)
+        }
+        Err(e) => {
+            eprintln!("{}: assembly error at {}", path.display(), e);
+            Err(EXIT_ASSEMBLY_ERROR)
         }
     }
-    println!("Program finished");
+}
+
+fn export(path: &Path, output: Option<PathBuf>, optimize: bool, verbose: bool) -> Result<i32, i32> {
+    let file = open(path, verbose)?;
+    let exported = if optimize {
+        file.export_optimized()
+    } else {
+        file.export().map(|data| (data, Vec::new()))
+    };
+    let (data, changes) = match exported {
+        Ok(exported) => exported,
+        Err(e) => {
+            eprintln!("Assembly error at {}", e);
+            return Err(EXIT_ASSEMBLY_ERROR);
+        }
+    };
+    for change in changes.iter() {
+        eprintln!("{}: {}", place(path, &change.location), change.message);
+    }
+    if verbose {
+        eprintln!("Exported data: {}", data);
+    }
+    // the binary file is next to the program unless another name is given
+    let output = output.unwrap_or_else(|| path.with_extension("bin"));
+    // cut the string into packages of 8 bits
+    let bytes: Vec<u8> = data
+        .as_bytes()
+        .chunks(8)
+        .map(|chunk| u8::from_str_radix(std::str::from_utf8(chunk).unwrap(), 2).unwrap())
+        .collect();
+    if let Err(e) = std::fs::write(&output, bytes) {
+        eprintln!("Cannot write {}: {}", output.display(), e);
+        return Err(EXIT_USAGE);
+    }
+    Ok(0)
+}
+
+fn fmt(files: &[PathBuf], check: bool, verbose: bool) -> Result<i32, i32> {
+    let mut unformatted = false;
+    for path in files {
+        let source = read_source(path, verbose)?;
+        let formatted = match format_source(&source, Some(path)) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("{}: assembly error at {}", path.display(), e);
+                return Err(EXIT_ASSEMBLY_ERROR);
+            }
+        };
+        if formatted == source {
+            continue;
+        }
+        if check {
+            eprintln!("{} is not formatted", path.display());
+            unformatted = true;
+        } else if let Err(e) = std::fs::write(path, formatted) {
+            eprintln!("Cannot write {}: {}", path.display(), e);
+            return Err(EXIT_USAGE);
+        } else if verbose {
+            eprintln!("Formatted {}", path.display());
+        }
+    }
+    Ok(if unformatted { EXIT_CHECK_FAILED } else { 0 })
+}
+
+fn cfg(
+    args: ProgramArgs,
+    output: Option<PathBuf>,
+    counts: bool,
+    verbose: bool,
+) -> Result<i32, i32> {
+    let mut program = load(ProgramBuilder::new(), &args, verbose)?;
+    let graph = Cfg::new(&program);
+    let mut code = 0;
+    let profile = if counts {
+        let (profile, outcome) = profile(&mut program);
+        code = exit_code(&program, outcome);
+        Some(profile)
+    } else {
+        None
+    };
+    let output = output.unwrap_or_else(|| args.file.with_extension("dot"));
+    if let Err(e) = std::fs::write(&output, graph.to_dot(&program, profile.as_ref())) {
+        eprintln!("Cannot write {}: {}", output.display(), e);
+        return Err(EXIT_USAGE);
+    }
+    Ok(code)
+}
+
+fn stack(path: &Path, arch: Option<Architecture>, verbose: bool) -> Result<i32, i32> {
+    let file = open(path, verbose)?;
+    let mut program = program_for(arch);
+    match program.load(file) {
+        Ok(()) => {}
+        Err(e) => {
+            eprintln!("{}: assembly error at {}", path.display(), e);
+            return Err(EXIT_ASSEMBLY_ERROR);
+        }
+    }
+    let analysis = analyze(&program);
+    print!("{}", analysis.report(&program));
+    let warnings = analysis.warnings(&program);
+    for (location, message) in warnings.iter() {
+        eprintln!("{}: warning: {}", place(path, location), message);
+    }
+    Ok(if warnings.is_empty() {
+        0
+    } else {
+        EXIT_CHECK_FAILED
+    })
+}
+
+fn list(path: &Path, verbose: bool) -> Result<i32, i32> {
+    let source = read_source(path, verbose)?;
+    match listing(&source, Some(path)) {
+        Ok(listing) => print!("{}", listing),
+        Err(e) => {
+            eprintln!("{}: assembly error at {}", path.display(), e);
+            return Err(EXIT_ASSEMBLY_ERROR);
+        }
+    }
+    Ok(0)
+}
+
+fn lint(files: &[PathBuf], verbose: bool) -> Result<i32, i32> {
+    let mut found = false;
+    for path in files {
+        let source = read_source(path, verbose)?;
+        let warnings = match copper::lint::lint(&source, Some(path)) {
+            Ok(warnings) => warnings,
+            Err(e) => {
+                eprintln!("{}: assembly error at {}", path.display(), e);
+                return Err(EXIT_ASSEMBLY_ERROR);
+            }
+        };
+        for warning in warnings.iter() {
+            eprintln!(
+                "{}: warning: {}",
+                place(path, &warning.location),
+                warning.message
+            );
+        }
+        found |= !warnings.is_empty();
+    }
+    Ok(if found { EXIT_CHECK_FAILED } else { 0 })
+}
+
+fn disasm(path: &Path, output: Option<PathBuf>, verbose: bool) -> Result<i32, i32> {
+    let file = open(path, verbose)?;
+    let source = file.read().and_then(disassemble).map_err(|e| {
+        eprintln!("Assembly error at {}", e);
+        EXIT_ASSEMBLY_ERROR
+    })?;
+    match output {
+        Some(output) => {
+            if let Err(e) = std::fs::write(&output, source) {
+                eprintln!("Cannot write {}: {}", output.display(), e);
+                return Err(EXIT_USAGE);
+            }
+        }
+        None => print!("{}", source),
+    }
+    Ok(0)
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+    // `copper <filename>` is a shorthand for `copper run <filename>`
+    let commands = Cli::command();
+    let is_command =
+        |arg: &str| commands.find_subcommand(arg).is_some() || HELP_ARGUMENTS.contains(&arg);
+    let first = args[1..]
+        .iter()
+        .find(|arg| !arg.starts_with('-') || is_command(arg));
+    if first.is_some_and(|arg| !is_command(arg)) {
+        args.insert(1, "run".to_string());
+    }
+    let cli = match Cli::try_parse_from(args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            std::process::exit(if e.use_stderr() { EXIT_USAGE } else { 0 });
+        }
+    };
+    let verbose = cli.verbose;
+    let result = match cli.command {
+        Command::Run(args) => run(args, verbose),
+        Command::Debug(args) => debug(args, verbose),
+        Command::Check { file, arch } => check(&file, arch, verbose),
+        Command::Export {
+            file,
+            output,
+            optimize,
+        } => export(&file, output, optimize, verbose),
+        Command::Cfg {
+            program,
+            output,
+            counts,
+        } => cfg(program, output, counts, verbose),
+        Command::Stack { file, arch } => stack(&file, arch, verbose),
+        Command::List { file } => list(&file, verbose),
+        Command::Lint { files } => lint(&files, verbose),
+        Command::Fmt { files, check } => fmt(&files, check, verbose),
+        Command::Disasm { file, output } => disasm(&file, output, verbose),
+    };
+    std::process::exit(result.unwrap_or_else(|code| code));
 }
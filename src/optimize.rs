@@ -0,0 +1,269 @@
+use crate::arch::Architecture;
+use crate::enums::{AddressNames, Instruction, Label, Parameter};
+use crate::preprocess::Location;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// change made by the optimizer, with the place in the source it applies to
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    pub location: Location,
+    pub message: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+// instructions of the #CODE section with their place, rewritten by the passes
+struct Optimizer<'a> {
+    code: Vec<(Instruction, Location)>,
+    label_names: &'a AddressNames,
+    changes: Vec<Change>,
+    // largest constant operand of the machine
+    max_constant: usize,
+    // `LDA Tn Tn` reads Tn, which is an error in strict mode before Tn is written
+    strict: bool,
+}
+
+// rewrite the instructions of the #CODE section, and the place of each one, with
+// peephole rules until none of them applies, the labels are never removed
+pub fn optimize(
+    instructions: &mut Vec<Instruction>,
+    locations: &mut Vec<Location>,
+    label_names: &AddressNames,
+    architecture: &Architecture,
+    strict: bool,
+) -> Vec<Change> {
+    let mut optimizer = Optimizer {
+        code: instructions.drain(..).zip(locations.drain(..)).collect(),
+        label_names,
+        changes: Vec::new(),
+        max_constant: architecture.max_value() as usize,
+        strict,
+    };
+    loop {
+        let count = optimizer.changes.len();
+        if !optimizer.strict {
+            optimizer.remove_self_loads();
+        }
+        optimizer.merge_increments();
+        optimizer.thread_jumps();
+        optimizer.remove_jumps_to_next();
+        optimizer.remove_dead_code();
+        if optimizer.changes.len() == count {
+            break;
+        }
+    }
+    optimizer
+        .changes
+        .sort_by_key(|c| (c.location.file.clone(), c.location.line));
+    for (instruction, location) in optimizer.code {
+        instructions.push(instruction);
+        locations.push(location);
+    }
+    optimizer.changes
+}
+
+// point a branch or a jump to another label
+fn set_target(instruction: &mut Instruction, label: Label) {
+    match instruction {
+        Instruction::BEQ(_, _, target)
+        | Instruction::BNE(_, _, target)
+        | Instruction::BBG(_, _, target)
+        | Instruction::BSM(_, _, target)
+        | Instruction::FBEQ(_, _, target)
+        | Instruction::FBNE(_, _, target)
+        | Instruction::FBSM(_, _, target)
+        | Instruction::FBBG(_, _, target)
+        | Instruction::JMP(target) => *target = label,
+        _ => {}
+    }
+}
+
+impl Optimizer<'_> {
+    fn change(&mut self, location: Location, message: String) {
+        self.changes.push(Change { location, message });
+    }
+
+    fn label_name(&self, label: &Label) -> String {
+        match self.label_names.name(label.name) {
+            Some(name) => name.to_string(),
+            None => label.to_string(),
+        }
+    }
+
+    // index of the LABEL instruction of each label
+    fn label_indices(&self) -> HashMap<Label, usize> {
+        self.code
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (instruction, _))| match instruction {
+                Instruction::LABEL(label) => Some((*label, index)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // `LDA Tn Tn` loads a register with its own value
+    fn remove_self_loads(&mut self) {
+        let mut removed: Vec<Change> = Vec::new();
+        self.code
+            .retain(|(instruction, location)| match instruction {
+                Instruction::LDA(register, Parameter::Register(source)) if register == source => {
+                    removed.push(Change {
+                        location: location.clone(),
+                        message: format!(
+                            "removed {}, which does not change {}",
+                            instruction, register
+                        ),
+                    });
+                    false
+                }
+                _ => true,
+            });
+        self.changes.extend(removed);
+    }
+
+    // consecutive `INC Tn` become a single `ADD Tn n`
+    fn merge_increments(&mut self) {
+        let mut code: Vec<(Instruction, Location)> = Vec::with_capacity(self.code.len());
+        let mut pending = std::mem::take(&mut self.code).into_iter().peekable();
+        let mut merged: Vec<Change> = Vec::new();
+        while let Some((instruction, location)) = pending.next() {
+            let register = match instruction {
+                Instruction::INC(register) => register,
+                _ => {
+                    code.push((instruction, location));
+                    continue;
+                }
+            };
+            let mut count = 1;
+            while count < self.max_constant
+                && pending
+                    .next_if(|(next, _)| matches!(next, Instruction::INC(r) if *r == register))
+                    .is_some()
+            {
+                count += 1;
+            }
+            if count == 1 {
+                code.push((instruction, location));
+                continue;
+            }
+            merged.push(Change {
+                location: location.clone(),
+                message: format!(
+                    "merged {} INC {} into ADD {} {}",
+                    count, register, register, count
+                ),
+            });
+            code.push((
+                Instruction::ADD(register, Parameter::Constant(count as u32)),
+                location,
+            ));
+        }
+        self.code = code;
+        self.changes.extend(merged);
+    }
+
+    // a branch or a jump to a label followed by `JMP` goes directly to the last label
+    // of the chain
+    fn thread_jumps(&mut self) {
+        let indices = self.label_indices();
+        // label reached by a jump to `label`, following the chain of JMP
+        let destination = |label: Label| {
+            let mut label = label;
+            let mut visited: HashSet<Label> = HashSet::from([label]);
+            loop {
+                // a label defined in another #CODE section is left as it is
+                let start = match indices.get(&label) {
+                    Some(start) => *start,
+                    None => return label,
+                };
+                let next = self.code[start..]
+                    .iter()
+                    .find(|(i, _)| !matches!(i, Instruction::LABEL(_)));
+                match next {
+                    Some((Instruction::JMP(next), _)) if visited.insert(*next) => label = *next,
+                    _ => return label,
+                }
+            }
+        };
+        let mut threads: Vec<(usize, Label)> = Vec::new();
+        for (index, (instruction, _)) in self.code.iter().enumerate() {
+            if let Some(target) = instruction.target() {
+                let destination = destination(target);
+                if destination != target {
+                    threads.push((index, destination));
+                }
+            }
+        }
+        for (index, destination) in threads {
+            let (instruction, location) = &self.code[index];
+            let message = format!(
+                "{} to {} now goes directly to {}",
+                instruction.mnemonic(),
+                self.label_name(&instruction.target().unwrap()),
+                self.label_name(&destination)
+            );
+            let location = location.clone();
+            set_target(&mut self.code[index].0, destination);
+            self.change(location, message);
+        }
+    }
+
+    // `JMP` to a label that follows it, with only labels in between
+    fn remove_jumps_to_next(&mut self) {
+        let mut indices = self.label_indices();
+        let mut index = 0;
+        while index < self.code.len() {
+            if let (Instruction::JMP(label), location) = &self.code[index] {
+                let target = indices.get(label).copied().unwrap_or(0);
+                if target > index
+                    && self.code[index + 1..target]
+                        .iter()
+                        .all(|(i, _)| matches!(i, Instruction::LABEL(_)))
+                {
+                    let message = format!(
+                        "removed JMP {} to the next instruction",
+                        self.label_name(label)
+                    );
+                    let location = location.clone();
+                    self.code.remove(index);
+                    self.change(location, message);
+                    // the labels after the jump moved
+                    indices = self.label_indices();
+                    continue;
+                }
+            }
+            index += 1;
+        }
+    }
+
+    // instructions after `JMP` or `HLT` that no label leads to
+    fn remove_dead_code(&mut self) {
+        let mut index = 0;
+        while index < self.code.len() {
+            let instruction = &self.code[index].0;
+            if matches!(instruction, Instruction::JMP(_) | Instruction::HLT(_)) {
+                let end = self.code[index + 1..]
+                    .iter()
+                    .position(|(i, _)| matches!(i, Instruction::LABEL(_)))
+                    .map_or(self.code.len(), |p| index + 1 + p);
+                if end > index + 1 {
+                    let message = format!(
+                        "removed {} unreachable instruction(s) after {}",
+                        end - index - 1,
+                        instruction.mnemonic()
+                    );
+                    let location = self.code[index + 1].1.clone();
+                    self.code.drain(index + 1..end);
+                    self.change(location, message);
+                }
+            }
+            index += 1;
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::enums::{Instruction, Parameter, Register};
+use crate::program::{Program, RunOutcome};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+const STAGES: [&str; 5] = ["IF", "ID", "EX", "MEM", "WB"];
+
+// cycles at which an instruction goes through the pipeline stages
+struct Slot {
+    text: String,
+    // cycle of the fetch
+    fetch: u64,
+    // first cycle in the decode stage
+    decode_start: u64,
+    // last cycle in the decode stage, later than decode_start when stalled
+    decode_end: u64,
+    // register that caused the stall, if any
+    hazard: Option<Register>,
+}
+
+impl Slot {
+    fn stage_at(&self, cycle: u64) -> Option<usize> {
+        if cycle >= self.fetch && cycle < self.decode_start {
+            Some(0)
+        } else if cycle >= self.decode_start && cycle <= self.decode_end {
+            Some(1)
+        } else if cycle > self.decode_end && cycle <= self.decode_end + 3 {
+            Some((cycle - self.decode_end) as usize + 1)
+        } else {
+            None
+        }
+    }
+
+    fn writeback(&self) -> u64 {
+        self.decode_end + 3
+    }
+}
+
+// counters of the pipelined execution
+#[derive(Debug, Default)]
+pub struct PipelineStats {
+    pub cycles: u64,
+    pub instructions: u64,
+    pub data_stalls: u64,
+    pub control_flushes: u64,
+}
+
+impl fmt::Display for PipelineStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Cycles: {}", self.cycles)?;
+        writeln!(f, "Instructions: {}", self.instructions)?;
+        writeln!(f, "Data hazard stalls: {}", self.data_stalls)?;
+        writeln!(f, "Control hazard flushes: {}", self.control_flushes)?;
+        let cpi = if self.instructions == 0 {
+            0.0
+        } else {
+            self.cycles as f64 / self.instructions as f64
+        };
+        writeln!(f, "CPI: {:.2}", cpi)
+    }
+}
+
+// classic 5-stage pipeline (fetch/decode/execute/memory/writeback) executing a program in order.
+// Registers are read in ID, branches are resolved in EX and jumps in ID, the branch is predicted
+// as not taken and the register file is written in the first half of WB.
+pub struct Pipeline {
+    pub forwarding: bool,
+    pub diagram: bool,
+    pub stats: PipelineStats,
+    slots: VecDeque<Slot>,
+    // last instruction writing each register: (decode end, value only available after MEM)
+    writers: HashMap<Register, (u64, bool)>,
+    printed: u64,
+}
+
+impl Pipeline {
+    pub fn new(forwarding: bool) -> Pipeline {
+        Pipeline {
+            forwarding,
+            diagram: true,
+            stats: PipelineStats::default(),
+            slots: VecDeque::new(),
+            writers: HashMap::new(),
+            printed: 0,
+        }
+    }
+
+    // run the program to completion; the architectural state is updated by the program itself
+    // so the result is the same as `Program::run`
+    pub fn run(&mut self, program: &mut Program) -> RunOutcome {
+        if self.diagram {
+            let mut header = format!("{:>6} ", "Cycle");
+            for stage in STAGES {
+                header.push_str(&format!("| {:<16}", stage));
+            }
+            eprintln!("{}", header.trim_end());
+        }
+        // first cycle at which the next instruction can be fetched
+        let mut next_fetch: u64 = 1;
+        let mut last_decode: u64 = 0;
+        let outcome = loop {
+            if program.is_halted() {
+                break program.end_outcome();
+            }
+            let index = match program.step() {
+                Ok(index) => index,
+                Err(outcome) => break outcome,
+            };
+            let instruction = &program.instructions[index];
+            if let Instruction::LABEL(_) = instruction {
+                continue;
+            }
+            let fetch = next_fetch;
+            let decode_start = (fetch + 1).max(last_decode + 1);
+            // wait for the operands written by the instructions still in flight
+            let mut decode_end = decode_start;
+            let mut hazard: Option<Register> = None;
+            for register in instruction.registers_read() {
+                if let Some((writer_decode, from_memory)) = self.writers.get(&register) {
+                    let ready = if !self.forwarding {
+                        // read in ID once the writer is in WB
+                        writer_decode + 3
+                    } else if *from_memory {
+                        // forwarded from MEM to EX
+                        writer_decode + 2
+                    } else {
+                        // forwarded from EX to EX
+                        writer_decode + 1
+                    };
+                    if ready > decode_end {
+                        decode_end = ready;
+                        hazard = Some(register);
+                    }
+                }
+            }
+            self.stats.data_stalls += decode_end - decode_start;
+            if let Some(register) = instruction.register_written() {
+                let from_memory = matches!(
+                    instruction,
+                    Instruction::LDA(_, Parameter::Variable(_)) | Instruction::POP(_) | Instruction::IN(_)
+                );
+                self.writers.insert(register, (decode_end, from_memory));
+            }
+            // the instructions fetched after a taken branch are flushed
+            next_fetch = decode_start;
+            if program.counter() != index + 1 && !program.is_halted() {
+                let resolved = match instruction {
+                    Instruction::JMP(_) => decode_end,
+                    _ => decode_end + 1,
+                };
+                self.stats.control_flushes += resolved + 1 - decode_end;
+                next_fetch = resolved + 1;
+            }
+            last_decode = decode_end;
+            self.stats.instructions += 1;
+            self.slots.push_back(Slot {
+                text: instruction.to_string(),
+                fetch,
+                decode_start,
+                decode_end,
+                hazard,
+            });
+            self.stats.cycles = decode_end + 3;
+            // every cycle before this fetch is complete
+            self.print_until(fetch);
+        };
+        let end = self.stats.cycles + 1;
+        self.print_until(end);
+        outcome
+    }
+
+    // print the cycles before `cycle` and drop the instructions that left the pipeline
+    fn print_until(&mut self, cycle: u64) {
+        while self.printed + 1 < cycle {
+            self.printed += 1;
+            if self.diagram {
+                eprintln!("{}", self.cycle_line(self.printed));
+            }
+        }
+        while let Some(slot) = self.slots.front() {
+            if slot.writeback() > self.printed {
+                break;
+            }
+            self.slots.pop_front();
+        }
+    }
+
+    fn cycle_line(&self, cycle: u64) -> String {
+        let mut stages: Vec<String> = vec!["-".to_string(); STAGES.len()];
+        for slot in self.slots.iter() {
+            if let Some(stage) = slot.stage_at(cycle) {
+                stages[stage] = if stage == 1 && cycle < slot.decode_end {
+                    format!("{} *{}", slot.text, slot.hazard.unwrap())
+                } else {
+                    slot.text.clone()
+                };
+            }
+        }
+        let mut line = format!("{:>6} ", cycle);
+        for stage in stages {
+            line.push_str(&format!("| {:<16}", stage));
+        }
+        line.trim_end().to_string()
+    }
+}
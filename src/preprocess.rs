@@ -0,0 +1,576 @@
+use crate::arch::{Architecture, CLASSIC};
+use crate::constants::resolve_constants;
+use crate::program::AssemblyError;
+use crate::pseudo::is_mnemonic;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// place in the source of a line produced by the preprocessor
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Location {
+    // included file holding the line, None for the file being assembled
+    pub file: Option<String>,
+    pub line: usize,
+    // macros expanded to produce the line, the innermost first, with the place of their call
+    pub calls: Vec<(String, Location)>,
+    // new name and spelling in the source of the local and numeric labels of the line
+    pub labels: Vec<(String, String)>,
+}
+
+impl Location {
+    pub fn new(line: usize) -> Location {
+        Location {
+            file: None,
+            line,
+            calls: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+
+    // give back to the labels renamed in `text` the spelling of the source
+    pub fn original(&self, text: &str) -> String {
+        if self.labels.is_empty() {
+            return text.to_string();
+        }
+        let mut res = String::with_capacity(text.len());
+        let mut word = String::new();
+        let flush = |word: &mut String, res: &mut String| {
+            match self.labels.iter().find(|(name, _)| name == word) {
+                Some((_, original)) => res.push_str(original),
+                None => res.push_str(word),
+            }
+            word.clear();
+        };
+        for c in text.chars() {
+            if c.is_alphanumeric() || c == '_' {
+                word.push(c);
+            } else {
+                flush(&mut word, &mut res);
+                res.push(c);
+            }
+        }
+        flush(&mut word, &mut res);
+        res
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}", self.line)?;
+        if let Some(file) = &self.file {
+            write!(f, " of {}", file)?;
+        }
+        for (i, (name, call)) in self.calls.iter().enumerate() {
+            let separator = if i == 0 { " (" } else { ", " };
+            write!(f, "{}macro {} called at {}", separator, name, call)?;
+        }
+        if !self.calls.is_empty() {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+// body of a `%macro NAME a b ...` definition
+struct Macro {
+    parameters: Vec<String>,
+    // lines up to `%endmacro` with their place in the source
+    body: Vec<(String, Location)>,
+    // labels defined in the body, renamed at each expansion
+    labels: Vec<String>,
+}
+
+// lines of the files of a program, gathered by section
+#[derive(Default)]
+struct Sections {
+    // files being read, from the file being assembled to the last included one
+    reading: Vec<PathBuf>,
+    // every file read, a file included several times is only read once
+    read: HashSet<PathBuf>,
+    // lines before the first section of each file
+    before: Vec<(String, Location)>,
+    // first header of each section with the lines of the section in every file
+    data: Option<(String, Location)>,
+    data_lines: Vec<(String, Location)>,
+    code: Option<(String, Location)>,
+    code_lines: Vec<(String, Location)>,
+    // lines of the #CONST sections, which are only definitions of constants
+    constants: Vec<(String, Location)>,
+}
+
+// label defined by a line, which is the only kind of line ending with `:`
+fn label(text: &str) -> Option<&str> {
+    text.split(';').next().unwrap().trim().strip_suffix(':')
+}
+
+// resolve the includes, expand the macros and replace the constants of a source, each line being returned
+// with the place it comes from. The included files are found relative to `path`,
+// the file of the source, or to the current directory.
+pub fn preprocess(
+    source: &str,
+    path: Option<&Path>,
+) -> Result<Vec<(String, Location)>, AssemblyError> {
+    preprocess_for(source, path, CLASSIC)
+}
+
+// same as preprocess for a program run on `architecture` when it has no #CONFIG line
+pub fn preprocess_for(
+    source: &str,
+    path: Option<&Path>,
+    architecture: Architecture,
+) -> Result<Vec<(String, Location)>, AssemblyError> {
+    let mut sections = Sections::default();
+    if let Some(path) = path {
+        let path = path.canonicalize().unwrap_or(path.to_path_buf());
+        sections.reading.push(path.clone());
+        sections.read.insert(path);
+    }
+    let directory = path.and_then(|p| p.parent()).unwrap_or(Path::new(""));
+    sections.include(source, None, directory)?;
+    let mut lines = sections.before;
+    if let Some(header) = sections.data {
+        lines.push(header);
+        lines.extend(sections.data_lines);
+    }
+    if let Some(header) = sections.code {
+        lines.push(header);
+        lines.extend(sections.code_lines);
+    }
+    let lines = expand_macros(lines)?;
+    let lines = resolve_local_labels(lines)?;
+    resolve_constants(lines, sections.constants, architecture)
+}
+
+impl Sections {
+    // add the lines of a file to the sections, the code of the files it includes
+    // is placed after its own code so that a program starts with its first instruction
+    fn include(
+        &mut self,
+        source: &str,
+        file: Option<String>,
+        directory: &Path,
+    ) -> Result<(), AssemblyError> {
+        let mut includes: Vec<(PathBuf, Location)> = Vec::new();
+        // 0 before the first section, 1 in #DATA, 2 in #CODE and 3 in #CONST
+        let mut section = 0;
+        for (number, text) in source.split('\n').enumerate() {
+            let location = Location {
+                file: file.clone(),
+                line: number + 1,
+                calls: Vec::new(),
+                labels: Vec::new(),
+            };
+            let mut words = text.split(';').next().unwrap().split_whitespace();
+            if words.next() == Some("%include") {
+                let name = words.collect::<Vec<&str>>().join(" ");
+                let name = match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+                    Some(name) if !name.is_empty() => name,
+                    _ => {
+                        let message = "expected %include \"file\"".to_string();
+                        return Err(AssemblyError::at(&location, message));
+                    }
+                };
+                includes.push((directory.join(name), location));
+                continue;
+            }
+            if text.starts_with("#DATA") {
+                section = 1;
+                if self.data.is_none() {
+                    self.data = Some((text.to_string(), location));
+                }
+                continue;
+            }
+            if text.starts_with("#CODE") {
+                section = 2;
+                if self.code.is_none() {
+                    self.code = Some((text.to_string(), location));
+                }
+                continue;
+            }
+            if text.starts_with("#CONST") {
+                section = 3;
+                continue;
+            }
+            let line = (text.to_string(), location);
+            match section {
+                0 => self.before.push(line),
+                1 => self.data_lines.push(line),
+                2 => self.code_lines.push(line),
+                _ => self.constants.push(line),
+            }
+        }
+        for (path, location) in includes {
+            let name = path.display().to_string();
+            let source = match std::fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(e) => {
+                    let message = format!("cannot include {}: {}", name, e);
+                    return Err(AssemblyError::at(&location, message));
+                }
+            };
+            let canonical = path.canonicalize().unwrap_or(path.clone());
+            if self.reading.contains(&canonical) {
+                let message = format!("{} includes itself", name);
+                return Err(AssemblyError::at(&location, message));
+            }
+            if !self.read.insert(canonical.clone()) {
+                continue;
+            }
+            self.reading.push(canonical);
+            let directory = path.parent().unwrap_or(Path::new(""));
+            self.include(&source, Some(name), directory)?;
+            self.reading.pop();
+        }
+        Ok(())
+    }
+}
+
+// remove the definitions of macros and replace each call by the body of the macro
+fn expand_macros(lines: Vec<(String, Location)>) -> Result<Vec<(String, Location)>, AssemblyError> {
+    let mut macros: HashMap<String, Macro> = HashMap::new();
+    // lines outside of the definitions
+    let mut program: Vec<(String, Location)> = Vec::new();
+    // macro being defined, with the place of `%macro`
+    let mut definition: Option<(String, Macro, Location)> = None;
+    for (text, location) in lines {
+        let mut words = text.split(';').next().unwrap().split_whitespace();
+        match words.next() {
+            Some("%macro") => {
+                if definition.is_some() {
+                    let message = "%macro inside a macro".to_string();
+                    return Err(AssemblyError::at(&location, message));
+                }
+                let name = match words.next() {
+                    Some(name) => name.to_string(),
+                    None => {
+                        let message = "%macro without a name".to_string();
+                        return Err(AssemblyError::at(&location, message));
+                    }
+                };
+                if is_mnemonic(&name) {
+                    let message = format!("{} is an instruction and cannot be a macro", name);
+                    return Err(AssemblyError::at(&location, message));
+                }
+                if macros.contains_key(&name) {
+                    let message = format!("macro {} is already defined", name);
+                    return Err(AssemblyError::at(&location, message));
+                }
+                let body = Macro {
+                    parameters: words.map(|w| w.to_string()).collect(),
+                    body: Vec::new(),
+                    labels: Vec::new(),
+                };
+                definition = Some((name, body, location));
+            }
+            Some("%endmacro") => match definition.take() {
+                Some((name, mut body, _)) => {
+                    // the numeric labels can already be defined several times
+                    body.labels = body
+                        .body
+                        .iter()
+                        .filter_map(|(text, _)| label(text))
+                        .filter(|l| !l.starts_with(|c: char| c.is_ascii_digit()))
+                        .map(|l| l.to_string())
+                        .collect();
+                    macros.insert(name, body);
+                }
+                None => {
+                    let message = "%endmacro without %macro".to_string();
+                    return Err(AssemblyError::at(&location, message));
+                }
+            },
+            Some(word) if word.starts_with('%') => {
+                let message = format!("Unknown directive {}", word);
+                return Err(AssemblyError::at(&location, message));
+            }
+            _ => match &mut definition {
+                Some((_, body, _)) => body.body.push((text, location)),
+                None => program.push((text, location)),
+            },
+        }
+    }
+    if let Some((name, _, location)) = definition {
+        let message = format!("macro {} has no %endmacro", name);
+        return Err(AssemblyError::at(&location, message));
+    }
+
+    // the labels given to the expansions must differ from the labels of the source
+    let mut labels: HashSet<String> = program
+        .iter()
+        .chain(macros.values().flat_map(|m| m.body.iter()))
+        .filter_map(|(text, _)| label(text).map(|l| l.to_string()))
+        .collect();
+    let mut expanded: Vec<(String, Location)> = Vec::new();
+    let mut code = false;
+    for (text, location) in program {
+        if text.starts_with('#') {
+            code = text.starts_with("#CODE");
+        }
+        // the names of the #DATA section are not calls
+        if !code {
+            expanded.push((text, location));
+            continue;
+        }
+        expand(&macros, text, location, &mut labels, &mut expanded)?;
+    }
+    Ok(expanded)
+}
+
+// add a line of code to the result, replaced by the body of the macro when it is a call
+fn expand(
+    macros: &HashMap<String, Macro>,
+    text: String,
+    location: Location,
+    labels: &mut HashSet<String>,
+    expanded: &mut Vec<(String, Location)>,
+) -> Result<(), AssemblyError> {
+    let mut words = text.split(';').next().unwrap().split_whitespace();
+    let (name, body) = match words.next().and_then(|w| macros.get_key_value(w)) {
+        Some(found) => found,
+        None => {
+            expanded.push((text, location));
+            return Ok(());
+        }
+    };
+    let arguments: Vec<&str> = words.collect();
+    if arguments.len() != body.parameters.len() {
+        let message = format!(
+            "macro {} expects {} argument(s), found {}",
+            name,
+            body.parameters.len(),
+            arguments.len()
+        );
+        return Err(AssemblyError::at(&location, message));
+    }
+    if location.calls.iter().any(|(n, _)| n == name) {
+        let message = format!("macro {} calls itself", name);
+        return Err(AssemblyError::at(&location, message));
+    }
+    // the labels of the body get a number unique to this expansion, since the
+    // names can only hold letters and digits
+    let mut replacements: HashMap<&str, String> = HashMap::new();
+    for label in body.labels.iter() {
+        let name = (1..)
+            .map(|n| format!("{}{}", label, n))
+            .find(|name| !labels.contains(name))
+            .unwrap();
+        labels.insert(name.clone());
+        replacements.insert(label, name);
+    }
+    for (parameter, argument) in body.parameters.iter().zip(arguments) {
+        replacements.insert(parameter, argument.to_string());
+    }
+    let mut calls = location.calls.clone();
+    calls.insert(
+        0,
+        (
+            name.clone(),
+            Location {
+                calls: Vec::new(),
+                ..location
+            },
+        ),
+    );
+    for (text, line) in body.body.iter() {
+        let location = Location {
+            calls: calls.clone(),
+            ..line.clone()
+        };
+        expand(
+            macros,
+            substitute(text, &replacements),
+            location,
+            labels,
+            expanded,
+        )?;
+    }
+    Ok(())
+}
+
+// replace the names in a line of code that are parameters or labels of the macro,
+// a name can be part of a word such as `a[1]`, `a+1`, `n*2`, `LOOP:` or `.loop:`
+fn substitute(text: &str, replacements: &HashMap<&str, String>) -> String {
+    let code = text.split(';').next().unwrap();
+    let words: Vec<String> = code
+        .split_whitespace()
+        .map(|word| {
+            let mut res = String::new();
+            let mut name = String::new();
+            for c in word.chars().chain(std::iter::once(' ')) {
+                if c.is_alphanumeric() || (c == '.' && name.is_empty()) {
+                    name.push(c);
+                    continue;
+                }
+                match replacements.get(name.as_str()) {
+                    Some(replacement) => res.push_str(replacement),
+                    None => res.push_str(&name),
+                }
+                name.clear();
+                res.push(c);
+            }
+            res.pop();
+            res
+        })
+        .collect();
+    format!("    {}", words.join(" "))
+}
+
+// number `N` of a numeric label `N:`
+fn is_numeric(label: &str) -> bool {
+    !label.is_empty() && label.chars().all(|c| c.is_ascii_digit())
+}
+
+// give a name unique in the program to the local labels `.name`, scoped to the last
+// global label of the same file, and to the numeric labels `N:`, which can be defined
+// several times and are referenced as `Nf` for the next one and `Nb` for the previous one.
+// The lines of a macro belong to the file of the call, and its labels, renamed at each
+// call, do not open a scope.
+fn resolve_local_labels(
+    lines: Vec<(String, Location)>,
+) -> Result<Vec<(String, Location)>, AssemblyError> {
+    let global = |label: &str| !label.starts_with('.') && !is_numeric(label);
+    let mut labels: HashSet<String> = lines
+        .iter()
+        .filter_map(|(text, _)| label(text))
+        .filter(|l| global(l))
+        .map(|l| l.to_string())
+        .collect();
+    let mut fresh = |base: String| {
+        let name = (0..)
+            .map(|n| match n {
+                0 => base.clone(),
+                n => format!("{}{}", base, n),
+            })
+            .find(|name| !labels.contains(name))
+            .unwrap();
+        labels.insert(name.clone());
+        name
+    };
+
+    // global label opening the scope of each line of code, with its file
+    let mut scopes: Vec<Option<(Option<String>, Option<String>)>> = Vec::new();
+    // new name of the local labels of each scope
+    let mut locals: HashMap<(Option<String>, Option<String>, String), String> = HashMap::new();
+    // line and new name of each definition of the numeric labels
+    let mut numbers: HashMap<String, Vec<(usize, String)>> = HashMap::new();
+    let mut scope: (Option<String>, Option<String>) = (None, None);
+    let mut code = false;
+    for (index, (text, location)) in lines.iter().enumerate() {
+        if text.starts_with('#') {
+            code = text.starts_with("#CODE");
+        }
+        if !code || text.starts_with('#') {
+            scopes.push(None);
+            continue;
+        }
+        // file where the line is written, or where the macro producing it is called
+        let file = match location.calls.last() {
+            Some((_, call)) => &call.file,
+            None => &location.file,
+        };
+        if scope.0 != *file {
+            scope = (file.clone(), None);
+        }
+        match label(text) {
+            Some(name) if global(name) && location.calls.is_empty() => {
+                scope.1 = Some(name.to_string())
+            }
+            // the labels of a macro do not open a scope
+            Some(name) if global(name) => {}
+            Some(name) if is_numeric(name) => {
+                let renamed = fresh(format!("L{}at{}", name, location.line));
+                numbers
+                    .entry(name.to_string())
+                    .or_default()
+                    .push((index, renamed));
+            }
+            Some(name) => {
+                let key = (scope.0.clone(), scope.1.clone(), name.to_string());
+                if locals.contains_key(&key) {
+                    let message = format!("label {} is already defined", name);
+                    return Err(AssemblyError::at(location, message));
+                }
+                let base = format!("{}{}", scope.1.clone().unwrap_or_default(), &name[1..]);
+                locals.insert(key, fresh(base));
+            }
+            None => {}
+        }
+        scopes.push(Some(scope.clone()));
+    }
+    if locals.is_empty() && numbers.is_empty() {
+        return Ok(lines);
+    }
+
+    let mut resolved: Vec<(String, Location)> = Vec::with_capacity(lines.len());
+    for (index, (text, mut location)) in lines.into_iter().enumerate() {
+        let (file, global) = match &scopes[index] {
+            Some(scope) => scope.clone(),
+            None => {
+                resolved.push((text, location));
+                continue;
+            }
+        };
+        let error = |message: String| Err(AssemblyError::at(&location, message));
+        // new name of a local label, or of the numeric label defined at this line
+        let rename = |name: &str| -> Option<String> {
+            if let Some(definitions) = numbers.get(name) {
+                return definitions
+                    .iter()
+                    .find(|(i, _)| *i == index)
+                    .map(|(_, n)| n.clone());
+            }
+            locals
+                .get(&(file.clone(), global.clone(), name.to_string()))
+                .cloned()
+        };
+        if let Some(name) = label(&text) {
+            match rename(name) {
+                Some(renamed) => {
+                    location.labels.push((renamed.clone(), name.to_string()));
+                    resolved.push((format!("{}:", renamed), location));
+                }
+                None => resolved.push((text, location)),
+            }
+            continue;
+        }
+        let code = text.split(';').next().unwrap();
+        let mut words: Vec<String> = code.split_whitespace().map(|w| w.to_string()).collect();
+        let mut renamed_labels: Vec<(String, String)> = Vec::new();
+        // the first word is the mnemonic, or `.equ`
+        for word in words.iter_mut().skip(1) {
+            if word.starts_with('.') {
+                match rename(word) {
+                    Some(renamed) => {
+                        renamed_labels.push((renamed.clone(), word.clone()));
+                        *word = renamed;
+                    }
+                    None => return error(format!("label {} is not defined", word)),
+                }
+                continue;
+            }
+            let (number, direction) = word.split_at(word.len().saturating_sub(1));
+            if !is_numeric(number) || (direction != "f" && direction != "b") {
+                continue;
+            }
+            let definitions = numbers.get(number).map_or(&[][..], |d| d.as_slice());
+            let target = match direction {
+                "f" => definitions.iter().find(|(i, _)| *i > index),
+                _ => definitions.iter().rev().find(|(i, _)| *i < index),
+            };
+            match target {
+                Some((_, renamed)) => {
+                    renamed_labels.push((renamed.clone(), word.clone()));
+                    *word = renamed.clone();
+                }
+                None if direction == "f" => {
+                    return error(format!("no label {}: after this line", number))
+                }
+                None => return error(format!("no label {}: before this line", number)),
+            }
+        }
+        location.labels.extend(renamed_labels);
+        resolved.push((format!("    {}", words.join(" ")), location));
+    }
+    Ok(resolved)
+}
@@ -1,95 +1,469 @@
-use crate::enums::{AddressNames, Instruction, Label, Parameter, Register, Variable};
+use crate::arch::{Architecture, CLASSIC};
+use crate::cache::{Cache, CacheConfig};
+use crate::enums::{
+    AddressNames, Extension, FloatParameter, FloatRegister, Instruction, Label, OutputFormat,
+    Parameter, Register, Variable, FLOAT_REGISTERS,
+};
 use crate::file::{CoFile, LineCategory, LineType};
+use crate::io::{IoHandler, StdIo};
+use crate::optimize::{optimize, Change};
+use crate::preprocess::{preprocess_for, Location};
+use crate::timing::{CostModel, CycleStats};
 use indexmap::IndexMap;
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
 
 // macro to get the value of a parameter
 macro_rules! get_parameter {
     ($parameter:ident,$self:ident, $value:expr) => {
         match $parameter {
             Parameter::Variable(variable) => {
-                $value = $self.get_variable(*variable);
+                $value = $self.get_variable(*variable)?;
             }
             Parameter::Constant(constant) => {
-                $value = *constant;
+                $value = *constant as u64;
             }
             Parameter::Register(register) => {
-                $value = $self.get_register(*register);
+                $value = $self.get_register(*register)?;
             }
         }
     };
 }
 
+// macro to get the value of an operand of the FPU, a memory word holds the bits of a f32
+// and a constant is an integer
+macro_rules! get_float_parameter {
+    ($parameter:ident,$self:ident, $value:expr) => {
+        match $parameter {
+            FloatParameter::Variable(variable) => {
+                $value = f32::from_bits($self.get_variable(*variable)? as u32);
+            }
+            FloatParameter::Constant(constant) => {
+                $value = *constant as i32 as f32;
+            }
+            FloatParameter::Register(register) => {
+                $value = $self.get_float_register(*register)?;
+            }
+        }
+    };
+}
+
+// reason why a program stopped
+#[derive(Debug, PartialEq)]
+pub enum RunOutcome {
+    // HLT was executed, with its exit code (0 when it has no parameter)
+    Halted(u32),
+    // the counter went past the last instruction
+    Finished,
+    // the step limit of the program was reached
+    StepLimit,
+    // runtime error raised by the instruction at `index`
+    Trap { index: usize, message: String },
+}
+
+// error found while translating a line of a program, lines start at 1
+#[derive(Debug)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub message: String,
+    // included file holding the line, None for the file being assembled
+    pub file: Option<String>,
+    // macros expanded to produce the line, the innermost first, with the place of their call
+    pub calls: Vec<(String, Location)>,
+}
+
+impl AssemblyError {
+    // the labels renamed by the preprocessor are reported with their spelling in the source
+    pub fn at(location: &Location, message: String) -> AssemblyError {
+        AssemblyError {
+            line: location.line,
+            message: location.original(&message),
+            file: location.file.clone(),
+            calls: location.calls.clone(),
+        }
+    }
+
+    pub fn location(&self) -> Location {
+        Location {
+            file: self.file.clone(),
+            line: self.line,
+            calls: self.calls.clone(),
+            labels: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location(), self.message)
+    }
+}
+
+// options of a program, set before it is loaded
+#[derive(Default)]
+pub struct ProgramBuilder {
+    verbose: bool,
+    strict: bool,
+    optimize: bool,
+    max_steps: Option<u64>,
+    max_stack: Option<usize>,
+    architecture: Architecture,
+    cost_model: CostModel,
+    cache: Option<CacheConfig>,
+    io: Option<Box<dyn IoHandler>>,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> ProgramBuilder {
+        ProgramBuilder::default()
+    }
+
+    // print the registers and the memory at the end of the run
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    // trap on reading a register or a memory cell that was never written,
+    // instead of reading 0
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    // run the peephole optimizer on the code once it is loaded
+    pub fn optimize(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
+
+    // stop the program after this number of executed instructions
+    pub fn max_steps(mut self, steps: u64) -> Self {
+        self.max_steps = Some(steps);
+        self
+    }
+
+    // maximum number of values on the stack
+    pub fn max_stack(mut self, size: usize) -> Self {
+        self.max_stack = Some(size);
+        self
+    }
+
+    // machine running a program without #CONFIG
+    pub fn architecture(mut self, architecture: Architecture) -> Self {
+        self.architecture = architecture;
+        self
+    }
+
+    pub fn cost_model(mut self, cost_model: CostModel) -> Self {
+        self.cost_model = cost_model;
+        self
+    }
+
+    pub fn cache(mut self, config: CacheConfig) -> Self {
+        self.cache = Some(config);
+        self
+    }
+
+    // handler used by IN and OUT instead of stdin and stdout
+    pub fn io(mut self, io: Box<dyn IoHandler>) -> Self {
+        self.io = Some(io);
+        self
+    }
+
+    pub fn build(self) -> Program {
+        let mut program = Program::new();
+        program.configure(self.architecture);
+        program.verbose = self.verbose;
+        program.strict = self.strict;
+        program.optimize = self.optimize;
+        program.max_steps = self.max_steps;
+        program.max_stack = self.max_stack;
+        program.cost_model = self.cost_model;
+        program.cache = self.cache.map(|config| RefCell::new(Cache::new(config)));
+        if let Some(io) = self.io {
+            program.io = io;
+        }
+        program
+    }
+}
+
 pub struct Program {
+    // machine running the program, set by the #CONFIG line of the source
+    pub architecture: Architecture,
     pub instructions: Vec<Instruction>,
-    registers: HashMap<Register, u32>,
-    variable_names: AddressNames,
-    label_names: AddressNames,
-    label_indices: HashMap<Label, usize>,
-    stack: Vec<u32>,
-    memory: IndexMap<Variable, u32>,
+    // place in the source of each instruction
+    pub locations: Vec<Location>,
+    registers: HashMap<Register, u64>,
+    registers_written: HashSet<Register>,
+    float_registers: HashMap<FloatRegister, f32>,
+    float_registers_written: HashSet<FloatRegister>,
+    pub(crate) variable_names: AddressNames,
+    pub(crate) label_names: AddressNames,
+    pub(crate) label_indices: HashMap<Label, usize>,
+    stack: Vec<u64>,
+    memory: IndexMap<Variable, u64>,
     counter: usize,
     pub verbose: bool,
+    // trap on reading a register or a memory cell never written
+    pub strict: bool,
+    // rewrite the code with the peephole optimizer when it is loaded
+    pub optimize: bool,
+    // changes made by the optimizer
+    pub optimizations: Vec<Change>,
+    // accept jumps to labels that are not defined, such as the labels of the
+    // program including a library
+    pub(crate) external_labels: bool,
+    pub cost_model: CostModel,
+    pub stats: CycleStats,
+    pub cache: Option<RefCell<Cache>>,
+    max_steps: Option<u64>,
+    max_stack: Option<usize>,
+    steps: u64,
+    exit_code: Option<u32>,
+    pub io: Box<dyn IoHandler>,
 }
 
 impl Program {
     pub fn new() -> Program {
         Program {
+            architecture: CLASSIC,
             instructions: Vec::new(),
-            registers: HashMap::new(),
+            locations: Vec::new(),
+            registers: CLASSIC
+                .registers()
+                .iter()
+                .map(|register| (*register, 0))
+                .collect(),
+            registers_written: HashSet::new(),
+            float_registers: FLOAT_REGISTERS
+                .iter()
+                .map(|register| (*register, 0.0))
+                .collect(),
+            float_registers_written: HashSet::new(),
             variable_names: AddressNames::new(),
             label_names: AddressNames::new(),
             label_indices: HashMap::new(),
             stack: Vec::with_capacity(4096 / 32),
             memory: IndexMap::new(),
             counter: 0,
-            verbose: std::env::var("RUST_LOG").is_ok(),
+            verbose: false,
+            strict: false,
+            optimize: false,
+            optimizations: Vec::new(),
+            external_labels: false,
+            cost_model: CostModel::default(),
+            stats: CycleStats::default(),
+            cache: None,
+            max_steps: None,
+            max_stack: None,
+            steps: 0,
+            exit_code: None,
+            io: Box::new(StdIo::default()),
         }
     }
 
-    pub fn run(&mut self) {
-        while self.counter < self.instructions.len() {
-            // let t1 = std::time::Instant::now();
-            self.execute_instruction(self.counter);
-            self.counter += 1;
-        }
+    // assemble a program from the text of a .co file
+    pub fn from_source(source: &str) -> Result<Program, AssemblyError> {
+        let mut program = Program::new();
+        program.load_source(source)?;
+        Ok(program)
+    }
+
+    // load a program from the content of a .bin file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, AssemblyError> {
+        let mut program = Program::new();
+        program.load_bytes(bytes)?;
+        Ok(program)
+    }
+
+    pub fn run(&mut self) -> RunOutcome {
+        let outcome = loop {
+            if self.is_halted() {
+                break self.end_outcome();
+            }
+            if let Err(outcome) = self.step() {
+                break outcome;
+            }
+        };
         if self.verbose {
-            println!("Registers: {:?}", self.registers);
-            println!("Memory: {:?}", self.memory);
+            eprintln!("Registers: {:?}", self.registers);
+            eprintln!("Memory: {:?}", self.memory);
+        }
+        outcome
+    }
+
+    // execute the instruction under the counter and return its index,
+    // or the reason why the program stopped before it
+    pub fn step(&mut self) -> Result<usize, RunOutcome> {
+        if let Some(max_steps) = self.max_steps {
+            if self.steps >= max_steps {
+                return Err(RunOutcome::StepLimit);
+            }
+        }
+        self.steps += 1;
+        let index = self.counter;
+        if let Err(message) = self.execute_instruction(index) {
+            return Err(RunOutcome::Trap { index, message });
+        }
+        self.counter += 1;
+        Ok(index)
+    }
+
+    // outcome of a program that went through all its instructions or executed HLT
+    pub fn end_outcome(&self) -> RunOutcome {
+        match self.exit_code {
+            Some(code) => RunOutcome::Halted(code),
+            None => RunOutcome::Finished,
         }
     }
 
-    pub fn run_debug(&mut self) {
-        println!("Starting debug mode\n");
-        while self.counter < self.instructions.len() {
-            println!("Instruction: {:?}", self.instructions[self.counter]);
-            println!("Registers: {:?}", self.registers);
-            println!("Memory: {:?}", self.memory);
-            println!("Stack: {:?}", self.stack);
-            println!("Counter: {}", self.counter);
-            println!("Press enter to continue");
+    pub fn is_halted(&self) -> bool {
+        self.counter >= self.instructions.len()
+    }
+
+    // index of the next instruction to execute
+    pub fn counter(&self) -> usize {
+        self.counter
+    }
+
+    pub fn registers(&self) -> &HashMap<Register, u64> {
+        &self.registers
+    }
+
+    pub fn float_registers(&self) -> &HashMap<FloatRegister, f32> {
+        &self.float_registers
+    }
+
+    pub fn memory(&self) -> &IndexMap<Variable, u64> {
+        &self.memory
+    }
+
+    pub fn stack(&self) -> &[u64] {
+        &self.stack
+    }
+
+    // value of a variable declared in the #DATA section
+    pub fn variable(&self, name: &str) -> Option<u64> {
+        let address = self.variable_names.position(name)?;
+        self.memory.get(&Variable::from(address)).copied()
+    }
+
+    // override the value of a variable declared in the #DATA section,
+    // the name can be followed by an index for an array such as `A[2]`
+    pub fn set_variable_by_name(&mut self, name: &str, value: u64) -> Result<(), String> {
+        let (name, offset) = match name.split_once('[') {
+            Some((name, index)) => match index.strip_suffix(']').map(|i| i.parse::<u16>()) {
+                Some(Ok(offset)) => (name, offset),
+                _ => return Err(format!("invalid index in {}", name)),
+            },
+            None => (name, 0),
+        };
+        let address = match self.variable_names.position(name) {
+            Some(address) => address + offset,
+            None => return Err(format!("unknown variable {}", name)),
+        };
+        if !self.memory.contains_key(&Variable::from(address)) {
+            return Err(format!("index {} is out of {}", offset, name));
+        }
+        let value = value & self.architecture.word_mask();
+        self.memory.insert(Variable::from(address), value);
+        Ok(())
+    }
+
+    pub fn run_debug(&mut self) -> RunOutcome {
+        eprintln!("Starting debug mode\n");
+        let outcome = loop {
+            if self.is_halted() {
+                break self.end_outcome();
+            }
+            eprintln!("Instruction: {:?}", self.instructions[self.counter]);
+            eprintln!("Registers: {:?}", self.registers);
+            eprintln!("Float registers: {:?}", self.float_registers);
+            eprintln!("Memory: {:?}", self.memory);
+            eprintln!("Stack: {:?}", self.stack);
+            eprintln!("Counter: {}", self.counter);
+            eprintln!("Press enter to continue");
             let mut input = String::new();
             std::io::stdin().read_line(&mut input).unwrap();
-            self.execute_instruction(self.counter);
-            self.counter += 1;
-        }
-        println!("Registers: {:?}", self.registers);
-        println!("Memory: {:?}", self.memory);
+            if let Err(outcome) = self.step() {
+                break outcome;
+            }
+        };
+        eprintln!("Registers: {:?}", self.registers);
+        eprintln!("Memory: {:?}", self.memory);
+        outcome
     }
 
-    pub fn load(&mut self, file: CoFile) {
+    pub fn load(&mut self, file: CoFile) -> Result<(), AssemblyError> {
         // read the file
-        let buffer: Vec<LineType> = file.read();
+        match file.extension {
+            Extension::CO => {
+                let source = file.read_as_text()?;
+                self.load_source_at(&source, Path::new(&file.filename))
+            }
+            Extension::BIN => {
+                let buffer = file.read()?;
+                let locations = (1..=buffer.len()).map(Location::new).collect();
+                self.load_lines(buffer, locations)
+            }
+        }
+    }
+
+    // assemble the text of a program once its includes and macros are expanded,
+    // the included files are found relative to the current directory
+    pub fn load_source(&mut self, source: &str) -> Result<(), AssemblyError> {
+        let (buffer, locations) = preprocess_for(source, None, self.architecture)?
+            .into_iter()
+            .map(|(text, location)| (LineType::String(text), location))
+            .unzip();
+        self.load_lines(buffer, locations)
+    }
+
+    // same as load_source for the text of the file at `path`, the included files
+    // are found relative to it
+    pub fn load_source_at(&mut self, source: &str, path: &Path) -> Result<(), AssemblyError> {
+        let (buffer, locations) = preprocess_for(source, Some(path), self.architecture)?
+            .into_iter()
+            .map(|(text, location)| (LineType::String(text), location))
+            .unzip();
+        self.load_lines(buffer, locations)
+    }
 
+    pub fn load_bytes(&mut self, bytes: &[u8]) -> Result<(), AssemblyError> {
+        let buffer = LineType::from_bytes(bytes)
+            .map_err(|e| AssemblyError::at(&Location::new(0), e))?;
+        let locations = (1..=buffer.len()).map(Location::new).collect();
+        self.load_lines(buffer, locations)
+    }
+
+    // translate the lines, the errors of the translation are reported with the place
+    // of the line in the source
+    fn load_lines(
+        &mut self,
+        buffer: Vec<LineType>,
+        locations: Vec<Location>,
+    ) -> Result<(), AssemblyError> {
         // parse the file
         let mut current_category: LineCategory = LineCategory::NONE;
-        for line in buffer {
-            if line.is_category() {
-                current_category = line.get_category();
+        // labels used by the jumps, with the place of the first jump
+        let mut jumps: Vec<(Label, &Location)> = Vec::new();
+        for (line, location) in buffer.iter().zip(locations.iter()) {
+            let error = |message: &str| AssemblyError::at(location, message.to_string());
+            if let Some(config) = line.config() {
+                if current_category != LineCategory::NONE {
+                    return Err(error("#CONFIG must come before #DATA and #CODE"));
+                }
+                let architecture =
+                    config.map_err(|e| error(&format!("Invalid #CONFIG: {}", e)))?;
+                self.configure(architecture);
                 continue;
             }
-            if current_category == LineCategory::NONE {
-                panic!("Invalid category");
+            if line.is_category() {
+                current_category = line.get_category().map_err(|e| error(&e))?;
+                continue;
             }
             if line.is_empty() {
                 // skip empty lines
@@ -99,16 +473,70 @@ impl Program {
                 // skip comments
                 continue;
             }
-            let instruction: Instruction = line.translate(
-                &current_category,
-                &mut self.variable_names,
-                &mut self.label_names,
+            if current_category == LineCategory::NONE {
+                return Err(error("Invalid category"));
+            }
+            let instructions: Vec<Instruction> = line
+                .translate_all(&current_category, &mut self.variable_names, &mut self.label_names)
+                .map_err(|e| error(&e))?;
+            for instruction in instructions {
+                self.architecture
+                    .check(&instruction)
+                    .map_err(|e| error(&e))?;
+                if let Some(label) = instruction.target() {
+                    jumps.push((label, location));
+                }
+                self.add_instruction(instruction);
+                self.locations
+                    .resize(self.instructions.len(), location.clone());
+            }
+        }
+        // every label used by a jump must be defined
+        for (label, location) in jumps.into_iter().filter(|_| !self.external_labels) {
+            if !self.label_indices.contains_key(&label) {
+                return Err(AssemblyError::at(location, "Label not found".to_string()));
+            }
+        }
+        if self.optimize {
+            self.optimizations = optimize(
+                &mut self.instructions,
+                &mut self.locations,
+                &self.label_names,
+                &self.architecture,
+                self.strict,
             );
-            self.add_instruction(instruction);
+            // the labels moved with the removed instructions
+            self.label_indices.clear();
+            for (index, instruction) in self.instructions.iter().enumerate() {
+                if let Instruction::LABEL(label) = instruction {
+                    self.label_indices.insert(*label, index);
+                }
+            }
         }
+        Ok(())
+    }
+
+    // set the machine running the program, its registers start at 0
+    pub fn configure(&mut self, architecture: Architecture) {
+        self.architecture = architecture;
+        self.registers = architecture
+            .registers()
+            .iter()
+            .map(|register| (*register, 0))
+            .collect();
     }
 
     pub fn add_instruction(&mut self, instruction: Instruction) {
+        // the values of #DATA keep the bits of the encoding of the machine
+        let instruction = match instruction {
+            Instruction::VARIABLE(variable, value) => {
+                Instruction::VARIABLE(variable, value & self.architecture.data_mask())
+            }
+            Instruction::ARRAY(variable, value, size) => {
+                Instruction::ARRAY(variable, value & self.architecture.data_mask(), size)
+            }
+            instruction => instruction,
+        };
         match instruction {
             Instruction::LABEL(label) => {
                 self.instructions.push(instruction);
@@ -123,9 +551,8 @@ impl Program {
                 self.memory.insert(variable, value);
                 // Add the rest of the variables without name
                 for i in 1..size {
-                    let name = variable.name + i as u16;
+                    let name = variable.name + i;
                     self.memory.insert(Variable::from(name), value);
-                    self.variable_names.add(&name.to_string());
                 }
             }
             _ => {
@@ -134,26 +561,57 @@ impl Program {
         }
     }
 
-    pub fn get_variable(&self, name: Variable) -> u32 {
-        if !self.memory.contains_key(&name) {
-            if self.verbose {
-                println!("Warning: Accessing uninitialized address {:?}!", name);
+    // value of a memory cell, a cell that was never declared nor written holds 0
+    // and reading it is an error in strict mode
+    pub fn get_variable(&self, name: Variable) -> Result<u64, String> {
+        if let Some(cache) = &self.cache {
+            cache.borrow_mut().access(name.name, false, self.counter);
+        }
+        match self.memory.get(&name) {
+            Some(value) => Ok(*value),
+            None if self.strict => Err(format!("Address {:?} is read before being written", name)),
+            None => {
+                if self.verbose {
+                    eprintln!("Warning: Accessing uninitialized address {:?}!", name);
+                }
+                Ok(0)
             }
-            return 0;
         }
-        *self.memory.get(&name).unwrap()
     }
 
-    pub fn set_variable(&mut self, name: Variable, value: u32) {
-        self.memory.insert(name, value);
+    pub fn set_variable(&mut self, name: Variable, value: u64) {
+        if let Some(cache) = &self.cache {
+            cache.borrow_mut().access(name.name, true, self.counter);
+        }
+        self.memory.insert(name, value & self.architecture.word_mask());
+    }
+
+    // value of a register, the registers start at 0 and reading one that was
+    // never written is an error in strict mode
+    pub fn get_register(&self, register: Register) -> Result<u64, String> {
+        if self.strict && !self.registers_written.contains(&register) {
+            return Err(format!("{} is read before being written", register));
+        }
+        Ok(self.registers[&register])
+    }
+
+    pub fn set_register(&mut self, register: Register, value: u64) {
+        self.registers
+            .insert(register, value & self.architecture.word_mask());
+        self.registers_written.insert(register);
     }
 
-    pub fn get_register(&self, register: Register) -> u32 {
-        *self.registers.get(&register).unwrap()
+    // value of a floating-point register, with the same rules as the other registers
+    pub fn get_float_register(&self, register: FloatRegister) -> Result<f32, String> {
+        if self.strict && !self.float_registers_written.contains(&register) {
+            return Err(format!("{} is read before being written", register));
+        }
+        Ok(self.float_registers[&register])
     }
 
-    pub fn set_register(&mut self, register: Register, value: u32) {
-        self.registers.insert(register, value);
+    pub fn set_float_register(&mut self, register: FloatRegister, value: f32) {
+        self.float_registers.insert(register, value);
+        self.float_registers_written.insert(register);
     }
 
     pub fn find_label(&self, label: &Label) -> usize {
@@ -161,88 +619,122 @@ impl Program {
         *self.label_indices.get(label).expect("Label not found")
     }
 
-    pub fn execute_instruction(&mut self, index: usize) {
+    // name of a label as written in the source, `L<n>` for a binary program
+    pub fn label_name(&self, label: &Label) -> String {
+        match self.label_names.name(label.name) {
+            Some(name) => name.to_string(),
+            None => label.to_string(),
+        }
+    }
+
+    // text of an instruction with the names of the labels of the source
+    pub fn instruction_text(&self, index: usize) -> String {
+        let instruction = &self.instructions[index];
+        match instruction {
+            Instruction::LABEL(label) => format!("{}:", self.label_name(label)),
+            _ => match instruction.target() {
+                Some(label) => {
+                    let text = instruction.to_string();
+                    let operands = text.rsplit_once(' ').unwrap().0;
+                    format!("{} {}", operands, self.label_name(&label))
+                }
+                None => instruction.to_string(),
+            },
+        }
+    }
+
+    // execute one instruction, a runtime error is returned as a message
+    pub fn execute_instruction(&mut self, index: usize) -> Result<(), String> {
         match &self.instructions[index] {
             Instruction::LDA(register, parameter) => {
-                let value: u32;
+                let value: u64;
                 get_parameter!(parameter, self, value);
                 self.set_register(*register, value);
             }
             Instruction::STR(variable, parameter) => {
-                let value: u32;
+                let value: u64;
                 get_parameter!(parameter, self, value);
                 self.set_variable(*variable, value);
             }
             Instruction::PUSH(parameter) => {
-                let value: u32;
+                let value: u64;
                 get_parameter!(parameter, self, value);
-                self.stack.push(value);
+                if Some(self.stack.len()) == self.max_stack.or(self.architecture.stack) {
+                    return Err("Stack overflow".to_string());
+                }
+                self.stack.push(value & self.architecture.word_mask());
             }
             Instruction::POP(register) => {
-                let value: Option<u32> = self.stack.pop();
-                if value.is_none() {
-                    panic!("Stack is empty");
+                let value: Option<u64> = self.stack.pop();
+                match value {
+                    Some(value) => self.set_register(*register, value),
+                    None => return Err("Stack is empty".to_string()),
                 }
-                self.set_register(*register, value.unwrap());
             }
             Instruction::AND(register, parameter) => {
-                let value: u32;
+                let value: u64;
                 get_parameter!(parameter, self, value);
-                let result: u32 = self.get_register(*register) & value;
+                let result: u64 = self.get_register(*register)? & value;
                 self.set_register(*register, result);
             }
             Instruction::OR(register, parameter) => {
-                let value: u32;
+                let value: u64;
                 get_parameter!(parameter, self, value);
-                let result: u32 = self.get_register(*register) | value;
+                let result: u64 = self.get_register(*register)? | value;
                 self.set_register(*register, result);
             }
             Instruction::NOT(register) => {
-                let value: u32 = self.get_register(*register);
-                let result: u32 = !value;
+                let value: u64 = self.get_register(*register)?;
+                let result: u64 = !value;
                 self.set_register(*register, result);
             }
             Instruction::ADD(register, parameter) => {
-                let value: u32;
+                let value: u64;
                 get_parameter!(parameter, self, value);
-                let result: u32 = self.get_register(*register) + value;
+                let result: u64 = self.get_register(*register)?.wrapping_add(value);
                 self.set_register(*register, result);
             }
             Instruction::SUB(register, parameter) => {
-                let value: u32;
+                let value: u64;
                 get_parameter!(parameter, self, value);
-                let result: u32 = self.get_register(*register) - value;
+                let result: u64 = self.get_register(*register)?.wrapping_sub(value);
                 self.set_register(*register, result);
             }
             Instruction::MUL(register, parameter) => {
-                let value: u32;
+                let value: u64;
                 get_parameter!(parameter, self, value);
-                let result: u32 = self.get_register(*register) * value;
+                let result: u64 = self.get_register(*register)?.wrapping_mul(value);
                 self.set_register(*register, result);
             }
             Instruction::DIV(register, parameter) => {
-                let value: u32;
+                let value: u64;
                 get_parameter!(parameter, self, value);
-                let result: u32 = self.get_register(*register) / value;
+                let result: u64 = match self.get_register(*register)?.checked_div(value) {
+                    Some(result) => result,
+                    None => return Err("Division by zero".to_string()),
+                };
                 self.set_register(*register, result);
             }
             Instruction::MOD(register, parameter) => {
-                let value: u32;
+                let value: u64;
                 get_parameter!(parameter, self, value);
-                let result: u32 = self.get_register(*register) % value;
+                let result: u64 = match self.get_register(*register)?.checked_rem(value) {
+                    Some(result) => result,
+                    None => return Err("Division by zero".to_string()),
+                };
                 self.set_register(*register, result);
             }
             Instruction::INC(register) => {
-                let result: u32 = self.get_register(*register) + 1;
+                let result: u64 = self.get_register(*register)?.wrapping_add(1);
                 self.set_register(*register, result);
             }
             Instruction::DEC(register) => {
-                let result: u32 = self.get_register(*register) - 1;
+                let result: u64 = self.get_register(*register)?.wrapping_sub(1);
                 self.set_register(*register, result);
             }
             Instruction::BEQ(parameter_1, parameter_2, label) => {
-                let value_1: u32;
-                let value_2: u32;
+                let value_1: u64;
+                let value_2: u64;
                 get_parameter!(parameter_1, self, value_1);
                 get_parameter!(parameter_2, self, value_2);
                 if value_1 == value_2 {
@@ -250,8 +742,8 @@ impl Program {
                 }
             }
             Instruction::BNE(parameter_1, parameter_2, label) => {
-                let value_1: u32;
-                let value_2: u32;
+                let value_1: u64;
+                let value_2: u64;
                 get_parameter!(parameter_1, self, value_1);
                 get_parameter!(parameter_2, self, value_2);
                 if value_1 != value_2 {
@@ -259,8 +751,8 @@ impl Program {
                 }
             }
             Instruction::BBG(parameter_1, parameter_2, label) => {
-                let value_1: u32;
-                let value_2: u32;
+                let value_1: u64;
+                let value_2: u64;
                 get_parameter!(parameter_1, self, value_1);
                 get_parameter!(parameter_2, self, value_2);
                 if value_1 > value_2 {
@@ -268,8 +760,8 @@ impl Program {
                 }
             }
             Instruction::BSM(parameter_1, parameter_2, label) => {
-                let value_1: u32;
-                let value_2: u32;
+                let value_1: u64;
+                let value_2: u64;
                 get_parameter!(parameter_1, self, value_1);
                 get_parameter!(parameter_2, self, value_2);
                 if value_1 < value_2 {
@@ -277,26 +769,32 @@ impl Program {
                 }
             }
             Instruction::SRL(register, offset) => {
-                let value: u32 = self.get_register(*register);
-                let result: u32 = value << offset;
+                let value: u64 = self.get_register(*register)?;
+                let result: u64 = value.checked_shl(*offset as u32).unwrap_or(0);
                 self.set_register(*register, result);
             }
             Instruction::SRR(register, offset) => {
-                let value: u32 = self.get_register(*register);
-                let result: u32 = value >> offset;
+                let value: u64 = self.get_register(*register)?;
+                let result: u64 = value.checked_shr(*offset as u32).unwrap_or(0);
                 self.set_register(*register, result);
             }
             Instruction::JMP(label) => {
                 self.counter = self.find_label(label);
             }
             Instruction::LABEL(_) => {}
-            Instruction::HLT => {
+            Instruction::HLT(parameter) => {
+                let mut code: u64 = 0;
+                if let Some(parameter) = parameter {
+                    get_parameter!(parameter, self, code);
+                }
+                self.exit_code = Some(code as u32);
                 self.counter = self.instructions.len();
             }
             Instruction::IN(parameter) => {
-                let mut input = String::new();
-                std::io::stdin().read_line(&mut input).unwrap();
-                let input: u32 = input.trim().parse().unwrap();
+                let input: u64 = match self.io.read() {
+                    Some(input) => input,
+                    None => return Err("No valid input available".to_string()),
+                };
                 match parameter {
                     Parameter::Variable(variable) => {
                         self.set_variable(*variable, input);
@@ -305,18 +803,128 @@ impl Program {
                         self.set_register(*register, input);
                     }
                     _ => {
-                        panic!("Cannot read to constant");
+                        return Err("Cannot read to constant".to_string());
                     }
                 }
             }
-            Instruction::OUT(parameter) => {
-                let value: u32;
+            Instruction::OUT(parameter, format) => {
+                let value: u64;
+                get_parameter!(parameter, self, value);
+                // a signed value is written with the sign bit of the word of the machine
+                let value = match format {
+                    OutputFormat::Signed => self.architecture.sign_extend(value) as u64,
+                    _ => value,
+                };
+                match format.format(value) {
+                    Some(text) => self.io.write(&text),
+                    None => return Err("Invalid character".to_string()),
+                }
+            }
+            Instruction::GETC(parameter) => {
+                let input: u64 = match self.io.read_char() {
+                    Some(input) => input as u64,
+                    None => return Err("No valid input available".to_string()),
+                };
+                match parameter {
+                    Parameter::Variable(variable) => {
+                        self.set_variable(*variable, input);
+                    }
+                    Parameter::Register(register) => {
+                        self.set_register(*register, input);
+                    }
+                    _ => {
+                        return Err("Cannot read to constant".to_string());
+                    }
+                }
+            }
+            Instruction::PRINTS(variable) => {
+                // print the characters up to the terminating zero
+                let mut text = String::new();
+                let mut address = variable.name;
+                loop {
+                    let value = self.get_variable(Variable::from(address))?;
+                    if value == 0 {
+                        break;
+                    }
+                    match u32::try_from(value).ok().and_then(char::from_u32) {
+                        Some(c) => text.push(c),
+                        None => return Err("Invalid character".to_string()),
+                    }
+                    address += 1;
+                }
+                self.io.write(&text);
+            }
+            Instruction::FLDA(register, parameter) => {
+                let value: f32;
+                get_float_parameter!(parameter, self, value);
+                self.set_float_register(*register, value);
+            }
+            Instruction::FSTR(variable, register) => {
+                let value: f32 = self.get_float_register(*register)?;
+                self.set_variable(*variable, value.to_bits() as u64);
+            }
+            // the operations follow IEEE-754, a division by zero gives an infinity
+            Instruction::FADD(register, parameter)
+            | Instruction::FSUB(register, parameter)
+            | Instruction::FMUL(register, parameter)
+            | Instruction::FDIV(register, parameter) => {
+                let value: f32;
+                get_float_parameter!(parameter, self, value);
+                let current: f32 = self.get_float_register(*register)?;
+                let result: f32 = match &self.instructions[index] {
+                    Instruction::FADD(_, _) => current + value,
+                    Instruction::FSUB(_, _) => current - value,
+                    Instruction::FMUL(_, _) => current * value,
+                    _ => current / value,
+                };
+                self.set_float_register(*register, result);
+            }
+            // a comparison with NaN is false, FBNE is taken
+            Instruction::FBEQ(register, parameter, label)
+            | Instruction::FBNE(register, parameter, label)
+            | Instruction::FBSM(register, parameter, label)
+            | Instruction::FBBG(register, parameter, label) => {
+                let value: f32;
+                get_float_parameter!(parameter, self, value);
+                let current: f32 = self.get_float_register(*register)?;
+                let taken = match &self.instructions[index] {
+                    Instruction::FBEQ(_, _, _) => current == value,
+                    Instruction::FBNE(_, _, _) => current != value,
+                    Instruction::FBSM(_, _, _) => current < value,
+                    _ => current > value,
+                };
+                if taken {
+                    self.counter = self.find_label(label);
+                }
+            }
+            Instruction::ITOF(register, parameter) => {
+                let value: u64;
                 get_parameter!(parameter, self, value);
-                println!("{}", value);
+                // the integer is read with the sign bit of the word of the machine
+                let value = self.architecture.sign_extend(value) as f32;
+                self.set_float_register(*register, value);
+            }
+            Instruction::FTOI(register, source) => {
+                // rounded toward zero, saturated at the limits of a signed word and 0 for NaN
+                let value: f32 = self.get_float_register(*source)?;
+                let max = (self.architecture.word_mask() >> 1) as i64;
+                let value = (value as i64).clamp(-max - 1, max);
+                self.set_register(*register, value as u64);
+            }
+            Instruction::FOUT(parameter) => {
+                let value: f32;
+                get_float_parameter!(parameter, self, value);
+                self.io.write(&format!("{}\n", value));
             }
             _ => {
-                panic!("Instruction not implemented");
+                return Err("Instruction not implemented".to_string());
             }
         }
+        // charge the cycles of the instruction on the abstract machine
+        let instruction = &self.instructions[index];
+        let taken = instruction.is_branch() && self.counter != index;
+        let cycles = self.cost_model.cost(instruction, taken);
+        self.stats.record(instruction, cycles);
+        Ok(())
     }
 }
@@ -0,0 +1,88 @@
+use crate::enums::{Register, MNEMONICS};
+use std::str::FromStr;
+
+// mnemonics that the assembler replaces by one or more instructions
+pub const PSEUDO_MNEMONICS: [&str; 9] = [
+    "MOV", "CLR", "BGE", "BLE", "BZ", "BNZ", "SWAP", "NEG", "XOR",
+];
+
+// names of the instructions and of the pseudo-instructions, which cannot be the name
+// of a macro or of a constant
+pub fn is_mnemonic(name: &str) -> bool {
+    MNEMONICS.contains(&name) || PSEUDO_MNEMONICS.contains(&name)
+}
+
+fn register(mnemonic: &str, operand: &str) -> Result<(), String> {
+    match Register::from_str(operand) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("{} expects registers, found {}", mnemonic, operand)),
+    }
+}
+
+// instructions replacing a line of code holding a pseudo-instruction, None for the
+// other lines
+pub fn expand(line: &str) -> Result<Option<Vec<String>>, String> {
+    let line = line.split(';').next().unwrap().trim();
+    let mut words = line.split_whitespace();
+    let mnemonic = match words.next() {
+        Some(mnemonic) => mnemonic,
+        None => return Ok(None),
+    };
+    let operands: Vec<&str> = words.collect();
+    let lines = match (mnemonic, operands.as_slice()) {
+        ("MOV", [a, b]) => vec![format!("LDA {} {}", a, b)],
+        ("CLR", [r]) => vec![format!("LDA {} 0", r)],
+        // a >= b when b < a or a == b
+        ("BGE", [a, b, label]) => vec![
+            format!("BSM {} {} {}", b, a, label),
+            format!("BEQ {} {} {}", a, b, label),
+        ],
+        ("BLE", [a, b, label]) => vec![
+            format!("BBG {} {} {}", b, a, label),
+            format!("BEQ {} {} {}", a, b, label),
+        ],
+        ("BZ", [a, label]) => vec![format!("BEQ {} 0 {}", a, label)],
+        ("BNZ", [a, label]) => vec![format!("BNE {} 0 {}", a, label)],
+        ("SWAP", [a, b]) => {
+            register(mnemonic, a)?;
+            register(mnemonic, b)?;
+            vec![
+                format!("PUSH {}", a),
+                format!("LDA {} {}", a, b),
+                format!("POP {}", b),
+            ]
+        }
+        // two's complement
+        ("NEG", [r]) => {
+            register(mnemonic, r)?;
+            vec![format!("NOT {}", r), format!("INC {}", r)]
+        }
+        // a ^ b = (a | b) & !(a & b), computed in a register saved on the stack since
+        // a and b alone cannot hold both halves
+        ("XOR", [a, b]) => {
+            register(mnemonic, a)?;
+            register(mnemonic, b)?;
+            if a == b {
+                return Ok(Some(vec![format!("LDA {} 0", a)]));
+            }
+            let scratch = ["T0", "T1", "T2"]
+                .into_iter()
+                .find(|r| r != a && r != b)
+                .unwrap();
+            vec![
+                format!("PUSH {}", scratch),
+                format!("LDA {} {}", scratch, a),
+                format!("AND {} {}", scratch, b),
+                format!("NOT {}", scratch),
+                format!("OR {} {}", a, b),
+                format!("AND {} {}", a, scratch),
+                format!("POP {}", scratch),
+            ]
+        }
+        _ if PSEUDO_MNEMONICS.contains(&mnemonic) => {
+            return Err(format!("Invalid operands for {}", mnemonic));
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(lines))
+}
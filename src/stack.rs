@@ -0,0 +1,259 @@
+use crate::cfg::Cfg;
+use crate::enums::Instruction;
+use crate::preprocess::Location;
+use crate::program::Program;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+// possible numbers of values on the stack, `max` being None when it can grow without bound
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Depth {
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+impl Depth {
+    fn hull(&self, other: &Depth) -> Depth {
+        Depth {
+            min: self.min.min(other.min),
+            max: match (self.max, other.max) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                _ => None,
+            },
+        }
+    }
+
+    // depth after an instruction, None when it always fails on an empty stack
+    fn after(&self, instruction: &Instruction) -> Option<Depth> {
+        match instruction {
+            Instruction::PUSH(_) => Some(Depth {
+                min: self.min + 1,
+                max: self.max.map(|m| m + 1),
+            }),
+            Instruction::POP(_) if self.max == Some(0) => None,
+            Instruction::POP(_) => Some(Depth {
+                min: self.min.saturating_sub(1),
+                max: self.max.map(|m| m - 1),
+            }),
+            _ => Some(*self),
+        }
+    }
+}
+
+impl fmt::Display for Depth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.max {
+            Some(max) if max == self.min => write!(f, "{}", max),
+            Some(max) => write!(f, "{}..{}", self.min, max),
+            None => write!(f, "{}..", self.min),
+        }
+    }
+}
+
+// POP that can run on an empty stack, with the blocks of a path leading to it
+#[derive(Debug)]
+pub struct Underflow {
+    pub index: usize,
+    // index of the first instruction of each block of the path
+    pub path: Vec<usize>,
+}
+
+// depth of the stack before each instruction, on every path of the program
+#[derive(Debug)]
+pub struct StackAnalysis {
+    // None for the instructions that can never be executed
+    pub depths: Vec<Option<Depth>>,
+    // None when the stack can grow without bound
+    pub max_depth: Option<u64>,
+    pub underflows: Vec<Underflow>,
+    // first instruction of the loops where the stack grows at each iteration
+    pub unbounded: Vec<usize>,
+}
+
+pub fn analyze(program: &Program) -> StackAnalysis {
+    let cfg = Cfg::new(program);
+    let count = program.instructions.len();
+    // no path without a loop can push more values than there are PUSH in the program
+    let pushes = program
+        .instructions
+        .iter()
+        .filter(|i| matches!(i, Instruction::PUSH(_)))
+        .count() as u64;
+
+    // depth when entering each block, widened to an unbounded depth at the start
+    // of a loop when it goes over the number of PUSH
+    let mut entries: Vec<Option<Depth>> = vec![None; cfg.blocks.len()];
+    let mut unbounded: Vec<usize> = Vec::new();
+    let mut pending: VecDeque<usize> = VecDeque::new();
+    if !cfg.blocks.is_empty() {
+        entries[0] = Some(Depth {
+            min: 0,
+            max: Some(0),
+        });
+        pending.push_back(0);
+    }
+    while let Some(block) = pending.pop_front() {
+        let mut depth = entries[block];
+        for index in cfg.blocks[block].start..cfg.blocks[block].end {
+            depth = depth.and_then(|d| d.after(&program.instructions[index]));
+        }
+        let depth = match depth {
+            Some(depth) => depth,
+            None => continue,
+        };
+        for (next, _) in cfg.blocks[block].successors.iter() {
+            let next = match next {
+                Some(next) => *next,
+                None => continue,
+            };
+            let mut merged = match entries[next] {
+                Some(entry) => entry.hull(&depth),
+                None => depth,
+            };
+            // every loop goes back to a block that is not after the current one
+            if next <= block && merged.max.is_some_and(|m| m > pushes) {
+                merged.max = None;
+                unbounded.push(cfg.blocks[next].start);
+            }
+            if entries[next] != Some(merged) {
+                entries[next] = Some(merged);
+                pending.push_back(next);
+            }
+        }
+    }
+
+    // the blocks follow each other from the first instruction to the last one
+    let mut depths: Vec<Option<Depth>> = Vec::with_capacity(count);
+    for (block, entry) in cfg.blocks.iter().zip(entries.iter()) {
+        let mut depth = *entry;
+        for instruction in program.instructions[block.start..block.end].iter() {
+            depths.push(depth);
+            depth = depth.and_then(|d| d.after(instruction));
+        }
+    }
+    let max_depth = depths
+        .iter()
+        .flatten()
+        .try_fold(0, |max, d| d.max.map(|m| max.max(m)));
+    let underflows = depths
+        .iter()
+        .enumerate()
+        .filter(|(index, depth)| {
+            matches!(program.instructions[*index], Instruction::POP(_))
+                && depth.is_some_and(|d| d.min == 0)
+        })
+        .map(|(index, _)| Underflow {
+            index,
+            path: underflow_path(program, &cfg, index, pushes),
+        })
+        .collect();
+    StackAnalysis {
+        depths,
+        max_depth,
+        underflows,
+        unbounded,
+    }
+}
+
+// shortest path of blocks from the start of the program to the POP at `target`
+// running on an empty stack, found by following the exact depth of the stack
+fn underflow_path(program: &Program, cfg: &Cfg, target: usize, pushes: u64) -> Vec<usize> {
+    let mut parents: HashMap<(usize, u64), Option<(usize, u64)>> = HashMap::new();
+    let mut pending: VecDeque<(usize, u64)> = VecDeque::new();
+    parents.insert((0, 0), None);
+    pending.push_back((0, 0));
+    while let Some((block, entry)) = pending.pop_front() {
+        let mut depth = Some(entry);
+        for index in cfg.blocks[block].start..cfg.blocks[block].end {
+            depth = match (&program.instructions[index], depth) {
+                (Instruction::POP(_), Some(0)) if index == target => {
+                    let mut path = vec![cfg.blocks[block].start];
+                    let mut state = parents[&(block, entry)];
+                    while let Some((block, depth)) = state {
+                        path.push(cfg.blocks[block].start);
+                        state = parents[&(block, depth)];
+                    }
+                    path.reverse();
+                    return path;
+                }
+                (Instruction::POP(_), Some(0)) => None,
+                (Instruction::POP(_), Some(d)) => Some(d - 1),
+                (Instruction::PUSH(_), Some(d)) => Some(d + 1),
+                (_, d) => d,
+            };
+        }
+        let depth = match depth {
+            Some(depth) if depth <= pushes + 1 => depth,
+            _ => continue,
+        };
+        for (next, _) in cfg.blocks[block].successors.iter() {
+            if let Some(next) = next {
+                if let Entry::Vacant(parent) = parents.entry((*next, depth)) {
+                    parent.insert(Some((block, entry)));
+                    pending.push_back((*next, depth));
+                }
+            }
+        }
+    }
+    Vec::new()
+}
+
+impl StackAnalysis {
+    // depth before each instruction followed by the problems found
+    pub fn report(&self, program: &Program) -> String {
+        let mut report = format!("{:>5}  {:<8}{}\n", "Line", "Depth", "Instruction");
+        for (index, depth) in self.depths.iter().enumerate() {
+            let depth = match depth {
+                Some(depth) => depth.to_string(),
+                None => "-".to_string(),
+            };
+            report.push_str(&format!(
+                "{:>5}  {:<8}{}\n",
+                program.locations.get(index).map_or(index, |l| l.line),
+                depth,
+                program.instruction_text(index)
+            ));
+        }
+        match self.max_depth {
+            Some(max) => report.push_str(&format!("Maximum stack depth: {}\n", max)),
+            None => report.push_str("Maximum stack depth: unbounded\n"),
+        }
+        report
+    }
+
+    // problems found by the analysis, with the line they refer to
+    pub fn warnings(&self, program: &Program) -> Vec<(Location, String)> {
+        let location = |index: usize| program.locations[index].clone();
+        let mut warnings: Vec<(Location, String)> = Vec::new();
+        for index in self.unbounded.iter() {
+            warnings.push((
+                location(*index),
+                "the stack grows at each iteration of this loop".to_string(),
+            ));
+        }
+        for underflow in self.underflows.iter() {
+            let path: Vec<String> = underflow
+                .path
+                .iter()
+                .map(|i| match location(*i) {
+                    Location {
+                        file: Some(file),
+                        line,
+                        ..
+                    } => format!("{} of {}", line, file),
+                    Location { line, .. } => line.to_string(),
+                })
+                .collect();
+            warnings.push((
+                location(underflow.index),
+                format!(
+                    "POP can run on an empty stack, through the lines {}",
+                    path.join(", ")
+                ),
+            ));
+        }
+        warnings.sort_by_key(|(l, _)| (l.file.clone(), l.line));
+        warnings
+    }
+}
@@ -0,0 +1,142 @@
+use crate::enums::{Instruction, MNEMONICS};
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+// cycles charged for each instruction of the abstract machine
+#[derive(Debug, Clone)]
+pub struct CostModel {
+    opcodes: HashMap<&'static str, u64>,
+    // extra cycles for each operand that reads or writes memory
+    pub memory_penalty: u64,
+    // extra cycles when a branch or a jump is taken
+    pub branch_penalty: u64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        let mut opcodes: HashMap<&'static str, u64> = HashMap::new();
+        // PUTC is OUT and has no cost of its own
+        for mnemonic in MNEMONICS.into_iter().filter(|m| *m != "PUTC") {
+            opcodes.insert(mnemonic, 1);
+        }
+        opcodes.insert("PUSH", 2);
+        opcodes.insert("POP", 2);
+        opcodes.insert("MUL", 3);
+        opcodes.insert("DIV", 10);
+        opcodes.insert("MOD", 10);
+        // the FPU takes a few more cycles than the ALU
+        opcodes.insert("FADD", 3);
+        opcodes.insert("FSUB", 3);
+        opcodes.insert("FMUL", 4);
+        opcodes.insert("FDIV", 12);
+        opcodes.insert("ITOF", 2);
+        opcodes.insert("FTOI", 2);
+        opcodes.insert("FOUT", 5);
+        opcodes.insert("IN", 5);
+        opcodes.insert("OUT", 5);
+        opcodes.insert("GETC", 5);
+        opcodes.insert("PRINTS", 5);
+        // labels are only markers and are never executed on the machine
+        opcodes.insert("LABEL", 0);
+        CostModel {
+            opcodes,
+            memory_penalty: 2,
+            branch_penalty: 2,
+        }
+    }
+}
+
+impl CostModel {
+    pub fn set_cost(&mut self, mnemonic: &'static str, cycles: u64) {
+        self.opcodes.insert(mnemonic, cycles);
+    }
+
+    pub fn cost(&self, instruction: &Instruction, taken: bool) -> u64 {
+        let mut cycles = *self.opcodes.get(instruction.mnemonic()).unwrap_or(&1);
+        cycles += self.memory_penalty * instruction.memory_operands() as u64;
+        if taken {
+            cycles += self.branch_penalty;
+        }
+        cycles
+    }
+}
+
+// parse a cost table where each line is `<OPCODE> <cycles>`,
+// `MEMORY <cycles>` or `BRANCH <cycles>`, with `;` comments. PUTC is the OUT
+// instruction in character mode and shares its cost.
+impl FromStr for CostModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut model = CostModel::default();
+        for (number, line) in s.lines().enumerate() {
+            let line = line.split(';').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let name = words.next().unwrap().to_uppercase();
+            let cycles = match words.next().map(|c| c.parse::<u64>()) {
+                Some(Ok(cycles)) => cycles,
+                _ => return Err(format!("line {}: expected a number of cycles", number + 1)),
+            };
+            match name.as_str() {
+                "MEMORY" => model.memory_penalty = cycles,
+                "BRANCH" => model.branch_penalty = cycles,
+                "PUTC" => model.set_cost("OUT", cycles),
+                _ => match model.opcodes.keys().find(|k| **k == name) {
+                    Some(mnemonic) => {
+                        let mnemonic = *mnemonic;
+                        model.set_cost(mnemonic, cycles);
+                    }
+                    None => return Err(format!("line {}: unknown opcode {}", number + 1, name)),
+                },
+            }
+        }
+        Ok(model)
+    }
+}
+
+// cycles and instructions executed by a program, in total and per opcode
+#[derive(Debug, Default)]
+pub struct CycleStats {
+    pub cycles: u64,
+    pub instructions: u64,
+    pub per_opcode: IndexMap<&'static str, (u64, u64)>,
+}
+
+impl CycleStats {
+    pub fn record(&mut self, instruction: &Instruction, cycles: u64) {
+        if let Instruction::LABEL(_) = instruction {
+            return;
+        }
+        self.cycles += cycles;
+        self.instructions += 1;
+        let entry = self.per_opcode.entry(instruction.mnemonic()).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += cycles;
+    }
+
+    // average number of cycles per executed instruction
+    pub fn cpi(&self) -> f64 {
+        if self.instructions == 0 {
+            return 0.0;
+        }
+        self.cycles as f64 / self.instructions as f64
+    }
+}
+
+impl fmt::Display for CycleStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Cycles: {}", self.cycles)?;
+        writeln!(f, "Instructions: {}", self.instructions)?;
+        writeln!(f, "CPI: {:.2}", self.cpi())?;
+        writeln!(f, "{:<8}{:>10}{:>10}", "Opcode", "Count", "Cycles")?;
+        for (mnemonic, (count, cycles)) in self.per_opcode.iter() {
+            writeln!(f, "{:<8}{:>10}{:>10}", mnemonic, count, cycles)?;
+        }
+        Ok(())
+    }
+}
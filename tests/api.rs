@@ -0,0 +1,96 @@
+use copper::arch::WIDE;
+use copper::enums::Register;
+use copper::program::{Program, ProgramBuilder, RunOutcome};
+
+const SUM: &str = "#DATA
+X 4
+Y[2] 0
+#CODE
+    LDA T0 X
+    ADD T0 3
+    PUSH T0
+    STR Y+1 T0
+";
+
+// a program runs one instruction at a time from its source, without a file on disk
+#[test]
+fn steps_and_accessors() {
+    let mut program = Program::from_source(SUM).unwrap();
+    assert_eq!(program.counter(), 0);
+    assert_eq!(program.step(), Ok(0));
+    assert_eq!(program.registers()[&Register::T0], 4);
+    assert_eq!(program.step(), Ok(1));
+    assert_eq!(program.counter(), 2);
+    assert_eq!(program.run(), RunOutcome::Finished);
+    assert!(program.is_halted());
+    assert_eq!(program.registers()[&Register::T0], 7);
+    assert_eq!(program.stack(), [7]);
+    assert_eq!(program.variable("X"), Some(4));
+    assert_eq!(program.variable("Z"), None);
+    assert_eq!(
+        program.memory().values().copied().collect::<Vec<_>>(),
+        [4, 0, 7]
+    );
+}
+
+#[test]
+fn set_variables_by_name() {
+    let mut program = Program::from_source(SUM).unwrap();
+    program.set_variable_by_name("X", 10).unwrap();
+    program.set_variable_by_name("Y[1]", 2).unwrap();
+    assert_eq!(
+        program.memory().values().copied().collect::<Vec<_>>(),
+        [10, 0, 2]
+    );
+    assert_eq!(
+        program.set_variable_by_name("Z", 1).unwrap_err(),
+        "unknown variable Z"
+    );
+    assert_eq!(
+        program.set_variable_by_name("Y[2]", 1).unwrap_err(),
+        "index 2 is out of Y"
+    );
+    assert_eq!(
+        program.set_variable_by_name("Y[x]", 1).unwrap_err(),
+        "invalid index in Y"
+    );
+    program.run();
+    assert_eq!(program.registers()[&Register::T0], 13);
+}
+
+#[test]
+fn builder_limits() {
+    let mut program = ProgramBuilder::new().max_steps(10).build();
+    program.load_source("#CODE\nloop:\nJMP loop\n").unwrap();
+    assert_eq!(program.run(), RunOutcome::StepLimit);
+
+    let mut program = ProgramBuilder::new().max_stack(2).build();
+    program
+        .load_source("#CODE\nPUSH 1\nPUSH 2\nPUSH 3\n")
+        .unwrap();
+    assert_eq!(
+        program.run(),
+        RunOutcome::Trap {
+            index: 2,
+            message: "Stack overflow".to_string()
+        }
+    );
+}
+
+// the options of a program do not leak into the programs built after it
+#[test]
+fn programs_are_independent() {
+    let source = "#CODE\nLDA T0 2000\nADD T0 2000\n";
+    let mut wide = ProgramBuilder::new()
+        .architecture(WIDE)
+        .strict(true)
+        .build();
+    wide.load_source(source).unwrap();
+    let mut classic = Program::new();
+    assert!(classic.load_source(source).is_err());
+    assert_eq!(wide.run(), RunOutcome::Finished);
+    assert_eq!(wide.registers()[&Register::T0], 4000);
+
+    let mut lenient = Program::from_source("#CODE\nLDA T0 T1\n").unwrap();
+    assert_eq!(lenient.run(), RunOutcome::Finished);
+}
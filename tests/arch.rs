@@ -0,0 +1,72 @@
+use copper::file::{export, LineType};
+use copper::lint::lint;
+use copper::program::Program;
+
+// the values of a machine with 8-bit words cannot use the 10 bits of the encoding
+#[test]
+fn small_values_fit_in_a_word() {
+    let source = "#CONFIG small\n#CODE\n.equ Y 500\nLDA T0 Y\nHLT\n";
+    let error = match Program::from_source(source) {
+        Ok(_) => panic!("constant 500 is accepted on a small machine"),
+        Err(error) => error,
+    };
+    assert!(error.message.contains("8 bits"), "{}", error.message);
+
+    let source = "#CONFIG small\n#CODE\nLDA T1 300\nHLT\n";
+    let error = match Program::from_source(source) {
+        Ok(_) => panic!("constant 300 is accepted on a small machine"),
+        Err(error) => error,
+    };
+    assert_eq!(
+        error.message,
+        "300 does not fit in the 8 bits of a constant"
+    );
+
+    let source = "#CONFIG small\n#DATA\nX 1000\n#CODE\nLDA T1 X\nHLT\n";
+    let messages: Vec<String> = lint(source, None)
+        .unwrap()
+        .into_iter()
+        .map(|w| w.message)
+        .collect();
+    assert!(messages
+        .contains(&"value 1000 of X does not fit in 8 bits and is stored as 232".to_string()));
+}
+
+// a 64-bit machine runs the whole #DATA values, but a binary program holds 10 bits
+#[test]
+fn classic64_values_cannot_be_exported() {
+    let source = "#CONFIG classic64\n#DATA\nX 100000000000\n#CODE\nLDA T0 X\nHLT\n";
+    assert!(Program::from_source(source).is_ok());
+    let error = export(LineType::from_text(source)).unwrap_err();
+    assert_eq!(error.line, 3);
+    assert!(error.message.contains("does not fit"), "{}", error.message);
+}
+
+#[test]
+fn invalid_data_value() {
+    let error = match Program::from_source("#DATA\nX 12a\n#CODE\nHLT\n") {
+        Ok(_) => panic!("value 12a is accepted"),
+        Err(error) => error,
+    };
+    assert_eq!(error.message, "invalid expression 12a: 12a is not a number");
+}
+
+// the classic encoding has 3 bits for a label
+#[test]
+fn classic_export_holds_eight_labels() {
+    let labels = |n: usize| {
+        let mut source = String::from("#CODE\n");
+        for i in 0..n {
+            source.push_str(&format!("L{}:\n    OUT {}\n", i, i));
+        }
+        source + "    HLT\n"
+    };
+    assert!(export(LineType::from_text(&labels(8))).is_ok());
+    let error = export(LineType::from_text(&labels(10))).unwrap_err();
+    assert!(
+        error.message.contains("Too many labels"),
+        "{}",
+        error.message
+    );
+    assert!(Program::from_source(&labels(10)).is_ok());
+}
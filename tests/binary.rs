@@ -0,0 +1,29 @@
+use copper::disasm::disassemble;
+use copper::file::{export, LineType};
+use copper::io::QueueIo;
+use copper::program::{Program, RunOutcome};
+
+// bytes of a binary program exported as a string of bits
+fn bytes(source: &str) -> Vec<u8> {
+    let bits = export(LineType::from_text(source)).unwrap();
+    bits.as_bytes()
+        .chunks(8)
+        .map(|chunk| u8::from_str_radix(std::str::from_utf8(chunk).unwrap(), 2).unwrap())
+        .collect()
+}
+
+// `V0 0` and `LDA T0 T0` are encoded as a zero word, which must not be skipped
+#[test]
+fn zero_words_round_trip() {
+    let source = "#DATA\nX 0\nY 5\n#CODE\nLDA T0 X\nOUT T0\nLDA T0 T0\nHLT\n";
+    let mut program = Program::from_bytes(&bytes(source)).unwrap();
+    let io = QueueIo::new(&[]);
+    program.io = Box::new(io.clone());
+    program.strict = true;
+    assert_eq!(program.run(), RunOutcome::Halted(0));
+    assert_eq!(io.output(), "0\n");
+
+    let text = disassemble(LineType::from_bytes(&bytes(source)).unwrap()).unwrap();
+    assert!(text.contains("V0 0\n"), "{}", text);
+    assert!(text.contains("LDA T0 T0\n"), "{}", text);
+}
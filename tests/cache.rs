@@ -0,0 +1,86 @@
+use copper::cache::{Cache, CacheConfig, CacheStats, Replacement, WritePolicy};
+use copper::program::{ProgramBuilder, RunOutcome};
+
+fn cache(options: &str) -> Cache {
+    Cache::new(options.parse().unwrap())
+}
+
+// accesses as (address, write), all made by the instruction 0
+fn stats(options: &str, accesses: &[(u16, bool)]) -> CacheStats {
+    let mut cache = cache(options);
+    for (address, write) in accesses {
+        cache.access(*address, *write, 0);
+    }
+    cache.stats
+}
+
+#[test]
+fn options() {
+    let config: CacheConfig = "size=8,line=2,ways=2,policy=fifo,write=through"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        (config.size, config.line_size, config.associativity),
+        (8, 2, 2)
+    );
+    assert_eq!(config.replacement, Replacement::Fifo);
+    assert_eq!(config.write_policy, WritePolicy::WriteThrough);
+    let error = |options: &str| options.parse::<CacheConfig>().unwrap_err();
+    assert_eq!(error("line"), "expected key=value, got line");
+    assert_eq!(error("ways=0"), "invalid value for ways: 0");
+    assert_eq!(error("colour=red"), "unknown cache option colour");
+    assert_eq!(error("policy=random"), "unknown replacement policy random");
+    assert_eq!(
+        error("size=6,line=4"),
+        "size must be a multiple of line size times associativity"
+    );
+}
+
+// the words of a line are loaded together, a line of another block of the same set evicts it
+#[test]
+fn direct_mapped() {
+    let stats = stats(
+        "size=8,line=2",
+        &[(0, false), (1, false), (8, false), (0, false)],
+    );
+    assert_eq!((stats.hits, stats.misses), (1, 3));
+    assert_eq!(stats.miss_rate(), 0.75);
+}
+
+// 0, 2 and 4 share the set of two lines, LRU evicts 2 and FIFO evicts 0
+#[test]
+fn replacement_policies() {
+    let accesses = [(0, false), (2, false), (0, false), (4, false), (0, false)];
+    let stats = self::stats("size=4,line=1,ways=2,policy=lru", &accesses);
+    assert_eq!((stats.hits, stats.misses), (2, 3));
+    let stats = self::stats("size=4,line=1,ways=2,policy=fifo", &accesses);
+    assert_eq!((stats.hits, stats.misses), (1, 4));
+}
+
+#[test]
+fn write_policies() {
+    let accesses = [(0, true), (2, false), (0, false)];
+    let stats = self::stats("size=2,line=1,write=back", &accesses);
+    assert_eq!((stats.hits, stats.misses), (0, 3));
+    assert_eq!((stats.writebacks, stats.memory_writes), (1, 0));
+    // a write miss does not load the line
+    let stats = self::stats("size=2,line=1,write=through", &[(0, true), (0, false)]);
+    assert_eq!((stats.hits, stats.misses), (0, 2));
+    assert_eq!((stats.writebacks, stats.memory_writes), (0, 1));
+}
+
+// the cells of an array are read from the line loaded by the first one
+#[test]
+fn program_accesses() {
+    let mut program = ProgramBuilder::new().cache(CacheConfig::default()).build();
+    program
+        .load_source("#DATA\nA[4] 1\n#CODE\nADD T0 A\nADD T0 A+1\nADD T0 A+2\nADD T0 A+3\nHLT\n")
+        .unwrap();
+    assert_eq!(program.run(), RunOutcome::Halted(0));
+    let cache = program.cache.as_ref().unwrap().borrow();
+    assert_eq!((cache.stats.hits, cache.stats.misses), (3, 1));
+    assert_eq!(cache.stats.per_instruction[&0], (0, 1));
+    assert_eq!(cache.stats.per_instruction[&3], (1, 0));
+    let report = cache.report(&program.instructions);
+    assert!(report.contains("100.00%"), "{}", report);
+}
@@ -0,0 +1,110 @@
+use copper::cfg::{profile, successors, Cfg, Edge};
+use copper::program::{Program, RunOutcome};
+use std::process::Command;
+
+// LDA, the loop of INC and BNE, then OUT and HLT
+const LOOP: &str = "#CODE
+    LDA T0 0
+loop:
+    INC T0
+    BNE T0 3 loop
+    OUT T0
+    HLT
+";
+
+#[test]
+fn basic_blocks() {
+    let program = Program::from_source(LOOP).unwrap();
+    let cfg = Cfg::new(&program);
+    let blocks: Vec<_> = cfg
+        .blocks
+        .iter()
+        .map(|b| (b.start, b.end, b.successors.clone()))
+        .collect();
+    assert_eq!(
+        blocks,
+        [
+            (0, 1, vec![(Some(1), Edge::Fallthrough)]),
+            (
+                1,
+                4,
+                vec![(Some(2), Edge::Fallthrough), (Some(1), Edge::Taken)]
+            ),
+            (4, 6, vec![(None, Edge::Jump)]),
+        ]
+    );
+    assert_eq!(cfg.block_of(3), Some(1));
+    assert_eq!(cfg.block_of(6), None);
+    assert_eq!(successors(&program, 3), [4, 1]);
+    assert_eq!(successors(&program, 5), []);
+}
+
+#[test]
+fn jumps_end_blocks() {
+    let program = Program::from_source("#CODE\nJMP end\nOUT T0\nend:\nHLT\n").unwrap();
+    let cfg = Cfg::new(&program);
+    assert_eq!(cfg.blocks.len(), 3);
+    assert_eq!(cfg.blocks[0].successors, [(Some(2), Edge::Jump)]);
+    assert_eq!(cfg.blocks[1].successors, [(Some(2), Edge::Fallthrough)]);
+}
+
+#[test]
+fn dot_with_counts() {
+    let mut program = Program::from_source(LOOP).unwrap();
+    let cfg = Cfg::new(&program);
+    let dot = cfg.to_dot(&program, None);
+    assert!(dot.starts_with("digraph program {\n"), "{}", dot);
+    assert!(
+        dot.contains("B1 -> B1 [label=\"taken\", color=\"darkgreen\"];\n"),
+        "{}",
+        dot
+    );
+    assert!(dot.contains("B2 -> end [label=\"\"];\n"), "{}", dot);
+
+    let (profile, outcome) = profile(&mut program);
+    assert_eq!(outcome, RunOutcome::Halted(0));
+    let dot = cfg.to_dot(&program, Some(&profile));
+    assert!(dot.contains("B1 (3x)\\l"), "{}", dot);
+    assert!(
+        dot.contains("B1 -> B1 [label=\"taken 2\", color=\"darkgreen\"];\n"),
+        "{}",
+        dot
+    );
+    assert!(
+        dot.contains("B1 -> B2 [label=\"fallthrough 1\"];\n"),
+        "{}",
+        dot
+    );
+    assert!(dot.contains("B2 -> end [label=\"1\"];\n"), "{}", dot);
+}
+
+// the graph is written next to the program unless another file is given
+#[test]
+fn cfg_command() {
+    let path = std::env::temp_dir().join(format!("copper-cfg-{}.co", std::process::id()));
+    std::fs::write(&path, LOOP).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_copper"))
+        .arg("cfg")
+        .arg(&path)
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(0));
+    let dot = std::fs::read_to_string(path.with_extension("dot")).unwrap();
+    assert!(dot.contains("B0 -> B1"), "{}", dot);
+
+    let other = path.with_extension("graph");
+    let output = Command::new(env!("CARGO_BIN_EXE_copper"))
+        .arg("cfg")
+        .arg("--counts")
+        .arg(&path)
+        .arg(&other)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    let dot = std::fs::read_to_string(&other).unwrap();
+    assert!(dot.contains("(3x)"), "{}", dot);
+    for path in [path.clone(), path.with_extension("dot"), other] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
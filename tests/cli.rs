@@ -0,0 +1,230 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+// program written in the temporary directory under a name unique to the test
+fn program(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("copper-{}-{}.co", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+fn run(path: &PathBuf) -> i32 {
+    Command::new(env!("CARGO_BIN_EXE_copper"))
+        .arg("run")
+        .arg(path)
+        .output()
+        .unwrap()
+        .status
+        .code()
+        .unwrap()
+}
+
+// the codes of HLT are above the ones of the interpreter
+#[test]
+fn halt_codes_do_not_overlap_interpreter_codes() {
+    let cases = [
+        ("#CODE\nHLT\n", 0),
+        ("#CODE\nHLT 0\n", 0),
+        ("#CODE\nHLT 3\n", 13),
+        ("#CODE\nHLT 244\n", 254),
+        ("#CODE\nHLT 300\n", 255),
+        ("#CODE\nPOP T0\nHLT\n", 3),
+        ("#CODE\nLDA T0 5000\n", 2),
+    ];
+    for (i, (source, code)) in cases.iter().enumerate() {
+        let path = program(&format!("halt{}", i), source);
+        assert_eq!(run(&path), *code, "{}", source);
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+// the values given with --input are echoed to stderr with -v only, stdout holds the output
+#[test]
+fn scripted_input_is_echoed_with_verbose_only() {
+    let path = program("echo", "#CODE\nIN T0\nOUT T0\nHLT\n");
+    let output = |verbose: bool| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_copper"));
+        if verbose {
+            command.arg("-v");
+        }
+        command
+            .arg("run")
+            .arg(&path)
+            .arg("--input")
+            .arg("7")
+            .output()
+            .unwrap()
+    };
+    let quiet = output(false);
+    assert_eq!(String::from_utf8_lossy(&quiet.stdout), "7\n");
+    assert!(!String::from_utf8_lossy(&quiet.stderr).contains("> 7"));
+    let verbose = output(true);
+    assert_eq!(String::from_utf8_lossy(&verbose.stdout), "7\n");
+    assert!(String::from_utf8_lossy(&verbose.stderr).contains("> 7\n"));
+    std::fs::remove_file(path).unwrap();
+}
+
+// check and stack assemble for the machine given with --arch, as run does
+#[test]
+fn check_and_stack_use_the_architecture() {
+    let path = program("arch", "#CODE\nLDA T9 2000\nHLT\n");
+    let status = |subcommand: &str, arch: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_copper"))
+            .arg(subcommand)
+            .args(arch)
+            .arg(&path)
+            .output()
+            .unwrap()
+            .status
+            .code()
+            .unwrap()
+    };
+    assert_eq!(status("check", &[]), 2);
+    assert_eq!(status("check", &["--arch", "wide"]), 0);
+    assert_eq!(status("stack", &[]), 2);
+    assert_eq!(status("stack", &["--arch", "wide"]), 0);
+    std::fs::remove_file(&path).unwrap();
+}
+
+// stdout and exit code of a command run on a program
+fn command(subcommand: &str, path: &PathBuf, args: &[&str]) -> (String, i32) {
+    let output = Command::new(env!("CARGO_BIN_EXE_copper"))
+        .arg(subcommand)
+        .arg(path)
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        output.status.code().unwrap(),
+    )
+}
+
+// the values of IN come from the command line or a file, the #DATA values can be overridden
+#[test]
+fn inputs_and_variables_from_the_command_line() {
+    let path = program(
+        "inputs",
+        "#DATA\nINPUTA 1\nB[2] 0\n#CODE\nIN T0\nIN T1\nADD T0 T1\n\
+         ADD T0 INPUTA\nADD T0 B+1\nOUT T0\nHLT\n",
+    );
+    let input = std::env::temp_dir().join(format!("copper-inputs-{}.txt", std::process::id()));
+    std::fs::write(&input, "10\n20\n").unwrap();
+    let input = input.to_str().unwrap();
+
+    assert_eq!(
+        command("run", &path, &["--input", "10,20"]),
+        ("31\n".to_string(), 0)
+    );
+    assert_eq!(
+        command("run", &path, &["--input-file", input]),
+        ("31\n".to_string(), 0)
+    );
+    assert_eq!(
+        command(
+            "run",
+            &path,
+            &["--input", "10 20", "--set", "INPUTA=100", "--set", "B[1]=5"]
+        ),
+        ("135\n".to_string(), 0)
+    );
+    // missing inputs are a trap
+    assert_eq!(command("run", &path, &["--input", "10"]).1, 3);
+    for args in [
+        &["--input", "10,x"][..],
+        &["--input", "1,2", "--input-file", input],
+        &["--input-file", "/nonexistent/copper-inputs.txt"],
+        &["--input", "1,2", "--set", "C=1"],
+        &["--input", "1,2", "--set", "INPUTA"],
+        &["--input", "1,2", "--set", "INPUTA=x"],
+        &["--input", "1,2", "--set", "B[2]=1"],
+    ] {
+        assert_eq!(command("run", &path, args).1, 1, "{:?}", args);
+    }
+    std::fs::remove_file(input).unwrap();
+    std::fs::remove_file(path).unwrap();
+}
+
+// the messages of the interpreter go to stderr, stdout only holds the output of the program
+#[test]
+fn interpreter_messages_are_on_stderr() {
+    let path = program("quiet", "#CODE\nLDA T0 5\nOUT T0\nHLT\n");
+    let output = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_copper"))
+            .args(args)
+            .arg(&path)
+            .output()
+            .unwrap();
+        (
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+    let (stdout, stderr) = output(&["run"]);
+    assert_eq!((stdout.as_str(), stderr.as_str()), ("5\n", ""));
+    let (stdout, stderr) = output(&["-v", "run"]);
+    assert_eq!(stdout, "5\n");
+    assert!(stderr.contains("Program finished"), "{}", stderr);
+    let (stdout, stderr) = output(&["-v", "run", "--quiet"]);
+    assert_eq!((stdout.as_str(), stderr.as_str()), ("5\n", ""));
+    let (stdout, stderr) = output(&["run", "--stats"]);
+    assert_eq!(stdout, "5\n");
+    assert!(stderr.contains("Time to run"), "{}", stderr);
+    assert!(!stderr.contains("Program finished"), "{}", stderr);
+    assert_eq!(command("run", &path, &["--quiet", "--stats"]).1, 1);
+    std::fs::remove_file(path).unwrap();
+}
+
+// the binary file is written next to the program, even in a directory named like a program
+#[test]
+fn export_output_paths() {
+    let directory = std::env::temp_dir().join(format!("copper-export-{}.co.d", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("sum.co");
+    std::fs::write(&path, "#CODE\nLDA T0 2\nADD T0 3\nOUT T0\nHLT\n").unwrap();
+
+    assert_eq!(command("export", &path, &[]), (String::new(), 0));
+    let binary = directory.join("sum.bin");
+    assert_eq!(command("run", &binary, &[]), ("5\n".to_string(), 0));
+    let other = directory.join("sum.data");
+    assert_eq!(command("export", &path, &[other.to_str().unwrap()]).1, 0);
+    assert_eq!(
+        std::fs::read(&other).unwrap(),
+        std::fs::read(&binary).unwrap()
+    );
+    let (source, code) = command("disasm", &binary, &[]);
+    assert_eq!(code, 0);
+    assert!(source.contains("ADD T0 3\n"), "{}", source);
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn subcommands() {
+    let valid = program("valid", "#CODE\nLDA T0 1\nOUT T0\nHLT\n");
+    let invalid = program("invalid", "#CODE\nLDA T0\nHLT\n");
+    // check assembles without running
+    assert_eq!(command("check", &valid, &[]), (String::new(), 0));
+    assert_eq!(command("check", &invalid, &[]).1, 2);
+    assert_eq!(command("export", &invalid, &[]).1, 2);
+    // a program without a command is run
+    let output = Command::new(env!("CARGO_BIN_EXE_copper"))
+        .arg(&valid)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    // files that are not programs and unknown options are usage errors
+    let text = valid.with_extension("txt");
+    std::fs::write(&text, "#CODE\nHLT\n").unwrap();
+    assert_eq!(command("run", &text, &[]).1, 1);
+    assert_eq!(command("check", &valid.with_extension("bin"), &[]).1, 1);
+    assert_eq!(command("check", &valid, &["--fast"]).1, 1);
+    let help = Command::new(env!("CARGO_BIN_EXE_copper"))
+        .args(["run", "--help"])
+        .output()
+        .unwrap();
+    assert_eq!(help.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&help.stdout).contains("--max-steps"));
+    for path in [valid, invalid, text] {
+        std::fs::remove_file(path).unwrap();
+    }
+}
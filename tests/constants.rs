@@ -0,0 +1,129 @@
+use copper::arch::WIDE;
+use copper::constants::evaluate;
+use copper::enums::Register;
+use copper::program::{Program, ProgramBuilder};
+
+fn error(source: &str) -> String {
+    match Program::from_source(source) {
+        Ok(_) => panic!("{} is accepted", source),
+        Err(error) => error.message,
+    }
+}
+
+// a constant cannot hide a register or an instruction
+#[test]
+fn constant_cannot_be_a_register_or_an_instruction() {
+    for name in ["T0", "F0", "LDA", "MOV", "GETC"] {
+        let message = error(&format!("#CODE\n.equ {} 3\nHLT\n", name));
+        assert!(message.contains("cannot be a constant"), "{}", message);
+    }
+}
+
+#[test]
+fn data_value_cannot_be_a_float() {
+    let message = error("#DATA\nX 1.5\n#CODE\nHLT\n");
+    assert!(message.starts_with("Invalid value 1.5"), "{}", message);
+}
+
+// a number written as an operand is checked even when no constant is defined
+#[test]
+fn literal_operand_is_range_checked() {
+    assert_eq!(
+        error("#CODE\nLDA T0 5000\nHLT\n"),
+        "5000 does not fit in the 10 bits of a constant"
+    );
+    assert_eq!(
+        error("#CODE\nADD T0 -1\nHLT\n"),
+        "-1 does not fit in the 10 bits of a constant"
+    );
+    assert!(Program::from_source("#CODE\nLDA T0 1023\nHLT\n").is_ok());
+}
+
+// without #CONFIG, the operands are checked for the machine the program is loaded on
+#[test]
+fn constants_fit_in_the_machine_of_the_program() {
+    let source = "#CODE\n.equ BIG 2000\nLDA T0 BIG\nADD T0 3000\nHLT\n";
+    assert!(error(source).contains("10 bits"));
+    let mut program = ProgramBuilder::new().architecture(WIDE).build();
+    program.load_source(source).unwrap();
+}
+
+fn value(expression: &str) -> Result<i64, String> {
+    evaluate(expression, &mut |name| match name {
+        "N" => Ok(4),
+        "SIZE" => Ok(10),
+        _ => Err(format!("unknown constant {}", name)),
+    })
+}
+
+#[test]
+fn expressions() {
+    assert_eq!(value("N*2+1"), Ok(9));
+    assert_eq!(value("SIZE-1"), Ok(9));
+    assert_eq!(value("1+N*SIZE%7"), Ok(6));
+    assert_eq!(value("(1+N)*(SIZE/3)"), Ok(15));
+    assert_eq!(value("-N+10"), Ok(6));
+    assert_eq!(value("(N+1"), Err("missing )".to_string()));
+    assert_eq!(
+        value("N*"),
+        Err("expected a number or a constant".to_string())
+    );
+    assert_eq!(value("M+1"), Err("unknown constant M".to_string()));
+}
+
+// the constants are replaced in the operands, the offsets, the lengths and the #DATA values
+#[test]
+fn constants_in_a_program() {
+    let source = "#CONST
+SIZE N+1
+N 3
+#DATA
+A[SIZE] N*2
+B SIZE*10
+#CODE
+.equ OFFSET SIZE-1
+    STR A+OFFSET 7
+    LDA T0 A[OFFSET]
+    LDA T1 B
+    LDA T2 N*N+1
+    LDA T3 A[N-2]
+";
+    let mut program = Program::from_source(source).unwrap();
+    program.run();
+    let registers: Vec<u64> = [Register::T0, Register::T1, Register::T2, Register::T3]
+        .iter()
+        .map(|r| program.registers()[r])
+        .collect();
+    assert_eq!(registers, [7, 40, 10, 6]);
+    assert_eq!(program.memory().len(), 5);
+}
+
+#[test]
+fn invalid_constants() {
+    assert_eq!(
+        error("#CONST\nA B+1\nB A\n#CODE\nHLT\n"),
+        "constant A is defined from itself"
+    );
+    assert_eq!(
+        error("#CODE\n.equ N 1\n.equ N 2\nHLT\n"),
+        "constant N is already defined"
+    );
+    assert_eq!(error("#CODE\n.equ N\nHLT\n"), "constant N has no value");
+    assert_eq!(
+        error("#CODE\n.equ N 2+\nHLT\n"),
+        "invalid expression 2+: expected a number or a constant"
+    );
+    assert_eq!(
+        error("#CODE\n.equ N 300\nLDA T0 N*4\nHLT\n"),
+        "N*4 is 1200, which does not fit in the 10 bits of a constant"
+    );
+    assert_eq!(
+        error("#CODE\nLDA T0 2*M\nHLT\n"),
+        "invalid expression 2*M: unknown constant M"
+    );
+    let mut program = Program::new();
+    let error = program
+        .load_source("#CODE\nHLT\n.equ N 2\nLDA T0 N-3\n")
+        .unwrap_err();
+    assert_eq!(error.line, 4);
+}
@@ -0,0 +1,44 @@
+use copper::file::{export, LineType};
+use copper::program::Program;
+
+// message of the error of a program that does not assemble
+fn error(source: &str) -> (usize, String) {
+    let mut program = Program::new();
+    let error = program.load_source(source).unwrap_err();
+    (error.line, error.message)
+}
+
+// the invalid lines are reported as errors with their place, the assembler does not panic
+#[test]
+fn invalid_lines_are_errors() {
+    let (line, message) = error("#CODE\nLDA T0 1\nADD T0\nHLT\n");
+    assert_eq!((line, message.as_str()), (3, "Missing operand"));
+    let (line, message) = error("#CODE\nNOT X\nHLT\n");
+    assert_eq!((line, message.as_str()), (2, "Invalid register X"));
+    let (_, message) = error("#CODE\nOUT T0 octal\nHLT\n");
+    assert_eq!(message, "Invalid output format octal");
+    let (_, message) = error("#CODE\nSWAP T0 5\nHLT\n");
+    assert_eq!(message, "SWAP expects registers, found 5");
+    let (_, message) = error("#DATA\nS \"a\\\"\n#CODE\nHLT\n");
+    assert_eq!(message, "Invalid escape sequence in string");
+}
+
+#[test]
+fn export_reports_invalid_lines() {
+    let error = export(LineType::from_text("#CODE\nJMP\n")).unwrap_err();
+    assert_eq!((error.line, error.message.as_str()), (2, "Missing operand"));
+    let error = export(LineType::from_text("#DATA\nX\n#CODE\nHLT\n")).unwrap_err();
+    assert_eq!((error.line, error.message.as_str()), (2, "X has no value"));
+}
+
+// a binary program with an unknown category or instruction is an error
+#[test]
+fn corrupt_binary_is_an_error() {
+    let mut program = Program::new();
+    assert!(program.load_bytes(&[0xFF, 0xFF, 0xFF, 0xFF]).is_err());
+    let mut program = Program::new();
+    let code = 0b1111101_u32 << 25;
+    let mut bytes = code.to_be_bytes().to_vec();
+    bytes.extend([0xFF, 0xFF, 0xFF, 0xFF]);
+    assert!(program.load_bytes(&bytes).is_err());
+}
@@ -0,0 +1,36 @@
+use copper::io::QueueIo;
+use copper::program::{ProgramBuilder, RunOutcome};
+use std::path::Path;
+
+// output of an example and the way it stopped, without the index of a trap since the
+// optimizer moves the instructions
+fn run(path: &Path, optimize: bool) -> (String, String) {
+    let io = QueueIo::new(&[5, 3, 7, 2, 9, 4, 6, 8]);
+    let mut program = ProgramBuilder::new()
+        .optimize(optimize)
+        .max_steps(1_000_000)
+        .io(Box::new(io.clone()))
+        .build();
+    let source = std::fs::read_to_string(path).unwrap();
+    program.load_source_at(&source, path).unwrap();
+    let outcome = match program.run() {
+        RunOutcome::Trap { message, .. } => format!("Trap: {}", message),
+        outcome => format!("{:?}", outcome),
+    };
+    (io.output(), outcome)
+}
+
+// the peephole optimizer does not change what the examples print nor how they end
+#[test]
+fn optimized_examples_are_equivalent() {
+    let mut count = 0;
+    for entry in std::fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|e| e.to_str()) != Some("co") {
+            continue;
+        }
+        assert_eq!(run(&path, false), run(&path, true), "{}", path.display());
+        count += 1;
+    }
+    assert!(count > 0);
+}
@@ -0,0 +1,71 @@
+use copper::file::{export, LineType};
+use copper::io::QueueIo;
+use copper::program::{Program, RunOutcome};
+
+fn output(mut program: Program) -> String {
+    let io = QueueIo::new(&[]);
+    program.io = Box::new(io.clone());
+    assert_eq!(program.run(), RunOutcome::Halted(0));
+    io.output()
+}
+
+// output of a program run from its source and from its binary file
+fn outputs(source: &str) -> (String, String) {
+    let bits = export(LineType::from_text(source)).unwrap();
+    let bytes: Vec<u8> = bits
+        .as_bytes()
+        .chunks(8)
+        .map(|chunk| u8::from_str_radix(std::str::from_utf8(chunk).unwrap(), 2).unwrap())
+        .collect();
+    (
+        output(Program::from_source(source).unwrap()),
+        output(Program::from_bytes(&bytes).unwrap()),
+    )
+}
+
+// F0 is 10^24, beyond the integers of 64 bits, F1 its opposite and F2 NaN
+const LARGE: &str = "FLDA F0 1000
+FMUL F0 F0
+FMUL F0 F0
+FMUL F0 F0
+FLDA F1 0
+FSUB F1 F0
+FLDA F2 0
+FDIV F2 F2
+";
+
+#[test]
+fn ftoi_saturates_at_the_limits_of_a_word() {
+    let source = format!(
+        "#CODE\n{}FTOI T0 F0\nOUT T0 signed\nFTOI T0 F1\nOUT T0 signed\nFTOI T0 F2\nOUT T0\nHLT\n",
+        LARGE
+    );
+    let (output, binary) = outputs(&source);
+    assert_eq!(output, "2147483647\n-2147483648\n0\n");
+    assert_eq!(binary, output);
+
+    let source = format!("#CONFIG classic64\n{}", source);
+    let output = self::output(Program::from_source(&source).unwrap());
+    assert_eq!(output, "9223372036854775807\n-9223372036854775808\n0\n");
+}
+
+#[test]
+fn ftoi_rounds_toward_zero() {
+    let source = "#CODE\nFLDA F0 7\nFLDA F1 2\nFDIV F0 F1\nFTOI T0 F0\nOUT T0\nFLDA F1 0\nFSUB F1 F0\nFTOI T0 F1\nOUT T0 signed\nHLT\n";
+    assert_eq!(outputs(source).0, "3\n-3\n");
+}
+
+// a negative #DATA value keeps the bits of the encoding in the source as in a binary file
+#[test]
+fn itof_of_negative_data_matches_the_binary_file() {
+    let source = "#DATA\nX -1\n#CODE\nITOF F0 X\nFOUT F0\nHLT\n";
+    assert_eq!(
+        outputs(source),
+        ("1023\n".to_string(), "1023\n".to_string())
+    );
+    let source = format!("#CONFIG wide\n{}", source);
+    assert_eq!(
+        outputs(&source),
+        ("65535\n".to_string(), "65535\n".to_string())
+    );
+}
@@ -0,0 +1,109 @@
+use copper::format::format_source;
+use copper::program::Program;
+use std::process::Command;
+
+// a library may jump to a label defined by the program including it
+#[test]
+fn format_library_jumping_to_caller() {
+    let source = "#CODE\nJMP done\n";
+    let formatted = format_source(source, None).expect("library not formatted");
+    assert_eq!(formatted, "#CODE\n    JMP done\n");
+}
+
+const MESSY: &str = "; sum of the numbers
+#DATA
+  X   5
+  TOTAL 0
+#CODE
+ LDA T0 X   ; first
+loop:  ; again
+      ADD   T0 1
+
+
+  BNE T0 9 loop
+; store
+STR TOTAL T0
+HLT
+";
+
+const FORMATTED: &str = "; sum of the numbers
+
+#DATA
+X     5
+TOTAL 0
+
+#CODE
+    LDA T0 X ; first
+loop:        ; again
+    ADD T0 1
+
+    BNE T0 9 loop
+    ; store
+    STR TOTAL T0
+    HLT
+";
+
+#[test]
+fn canonical_layout() {
+    assert_eq!(format_source(MESSY, None).unwrap(), FORMATTED);
+    assert_eq!(format_source(FORMATTED, None).unwrap(), FORMATTED);
+}
+
+// the formatted examples assemble to the same instructions and are already formatted
+#[test]
+fn examples_keep_their_instructions() {
+    for entry in std::fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|e| e.to_str()) != Some("co") {
+            continue;
+        }
+        let source = std::fs::read_to_string(&path).unwrap();
+        let formatted = format_source(&source, Some(&path)).unwrap();
+        assert_eq!(
+            format_source(&formatted, Some(&path)).unwrap(),
+            formatted,
+            "{}",
+            path.display()
+        );
+        let instructions = |source: &str| {
+            let mut program = Program::new();
+            program.load_source_at(source, &path).unwrap();
+            program.instructions
+        };
+        assert_eq!(
+            instructions(&formatted),
+            instructions(&source),
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn invalid_programs_are_not_formatted() {
+    let error = format_source("#CODE\n  LDA T0\n", None).unwrap_err();
+    assert_eq!(error.line, 2);
+}
+
+// `fmt --check` only reports, `fmt` rewrites the file
+#[test]
+fn fmt_command() {
+    let path = std::env::temp_dir().join(format!("copper-fmt-{}.co", std::process::id()));
+    std::fs::write(&path, MESSY).unwrap();
+    let fmt = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_copper"))
+            .arg("fmt")
+            .args(args)
+            .arg(&path)
+            .status()
+            .unwrap()
+            .code()
+            .unwrap()
+    };
+    assert_eq!(fmt(&["--check"]), 5);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), MESSY);
+    assert_eq!(fmt(&[]), 0);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), FORMATTED);
+    assert_eq!(fmt(&["--check"]), 0);
+    std::fs::remove_file(path).unwrap();
+}
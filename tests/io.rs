@@ -0,0 +1,108 @@
+use copper::enums::Register;
+use copper::io::{IoHandler, QueueIo, ScriptedIo, StdIo};
+use copper::program::{ProgramBuilder, RunOutcome};
+use std::cell::RefCell;
+use std::io::Cursor;
+use std::rc::Rc;
+
+fn run(source: &str, io: Box<dyn IoHandler>) -> RunOutcome {
+    let mut program = ProgramBuilder::new().io(io).build();
+    program.load_source(source).unwrap();
+    program.run()
+}
+
+// the factorial example runs without a terminal and its output is captured
+#[test]
+fn queue_input_and_output() {
+    let source = std::fs::read_to_string("examples/factorial.co").unwrap();
+    let io = QueueIo::new(&[5]);
+    assert_eq!(run(&source, Box::new(io.clone())), RunOutcome::Halted(0));
+    assert_eq!(io.output(), "120\n");
+    io.push_input(3);
+    assert_eq!(run(&source, Box::new(io.clone())), RunOutcome::Halted(0));
+    assert_eq!(io.output(), "120\n6\n");
+}
+
+#[test]
+fn missing_input_is_a_trap() {
+    let trap = RunOutcome::Trap {
+        index: 1,
+        message: "No valid input available".to_string(),
+    };
+    let source = "#CODE\nIN T0\nIN T1\n";
+    assert_eq!(run(source, Box::new(QueueIo::new(&[1]))), trap);
+    let reader = StdIo::from_reader(Cursor::new("1\nten\n"));
+    assert_eq!(run(source, Box::new(reader)), trap);
+}
+
+#[test]
+fn input_scripts() {
+    let source = "#DATA\nX 0\n#CODE\nIN X\nIN T0\nIN T1\nIN T2\n";
+    let script = ScriptedIo::parse("4, 5\n6 7").unwrap();
+    assert_eq!(run(source, Box::new(script)), RunOutcome::Finished);
+    assert!(ScriptedIo::parse("").is_ok());
+    assert_eq!(
+        ScriptedIo::parse("1,-2").err().unwrap(),
+        "invalid input value -2"
+    );
+}
+
+// handler counting the values it gives and keeping the lines it receives
+struct Counter {
+    count: u64,
+    lines: Rc<RefCell<Vec<String>>>,
+}
+
+impl IoHandler for Counter {
+    fn read(&mut self) -> Option<u64> {
+        self.count += 1;
+        Some(self.count)
+    }
+
+    fn write(&mut self, text: &str) {
+        self.lines.borrow_mut().push(text.to_string());
+    }
+}
+
+#[test]
+fn custom_handler() {
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let counter = Counter {
+        count: 0,
+        lines: lines.clone(),
+    };
+    let mut program = ProgramBuilder::new().io(Box::new(counter)).build();
+    program
+        .load_source("#CODE\nIN T0\nIN T1\nADD T0 T1\nOUT T0\n")
+        .unwrap();
+    assert_eq!(program.run(), RunOutcome::Finished);
+    assert_eq!(program.registers()[&Register::T0], 3);
+    assert_eq!(*lines.borrow(), ["3\n"]);
+}
+
+// IN reads the line after the one of GETC instead of the rest of it
+#[test]
+fn getc_then_in() {
+    let mut program = ProgramBuilder::new()
+        .io(Box::new(StdIo::from_reader(Cursor::new("a\n42\n"))))
+        .build();
+    program
+        .load_source("#CODE\n    GETC T0\n    IN T1\n")
+        .unwrap();
+    assert_eq!(program.run(), RunOutcome::Finished);
+    assert_eq!(program.registers()[&Register::T0], 'a' as u64);
+    assert_eq!(program.registers()[&Register::T1], 42);
+}
+
+#[test]
+fn getc_reads_the_rest_of_the_line() {
+    let mut program = ProgramBuilder::new()
+        .io(Box::new(StdIo::from_reader(Cursor::new("ab\n7\n"))))
+        .build();
+    program
+        .load_source("#CODE\n    GETC T0\n    GETC T1\n    IN T2\n")
+        .unwrap();
+    assert_eq!(program.run(), RunOutcome::Finished);
+    assert_eq!(program.registers()[&Register::T1], 'b' as u64);
+    assert_eq!(program.registers()[&Register::T2], 7);
+}
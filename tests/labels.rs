@@ -0,0 +1,41 @@
+use copper::lint::lint;
+use copper::listing::listing;
+
+const SOURCE: &str = "#CODE
+main:
+    LDA T0 3
+.loop:
+    SUB T0 1
+    BNE T0 0 .loop
+1:
+    JMP 1f
+1:
+    HLT
+";
+
+// the local and numeric labels are renamed when assembled, but reported as in the source
+#[test]
+fn lint_spells_labels_as_in_source() {
+    let messages: Vec<String> = lint(SOURCE, None)
+        .unwrap()
+        .into_iter()
+        .map(|w| w.message)
+        .collect();
+    assert!(messages.contains(&"label 1 is never jumped to".to_string()));
+    assert!(
+        messages.iter().all(|m| !m.contains("L1at")),
+        "{:?}",
+        messages
+    );
+}
+
+#[test]
+fn listing_spells_labels_as_in_source() {
+    let listing = listing(SOURCE, None).unwrap();
+    assert!(
+        listing.contains("BNE T0 0 .loop  BNE T0 0 .loop"),
+        "{}",
+        listing
+    );
+    assert!(listing.contains("JMP 1f          JMP 1f"), "{}", listing);
+}
@@ -0,0 +1,42 @@
+use copper::lint::lint;
+
+// the #DATA lines of an included file are checked with the file and the line they come from
+#[test]
+fn included_data_is_checked_at_its_place() {
+    let dir = std::env::temp_dir().join(format!("copper-lint-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("data.co"), "#DATA\n\nBIG 5000\nSPARE 1\n").unwrap();
+    let source = "%include \"data.co\"\n#CODE\nLDA T0 BIG\nOUT T0\nHLT\n";
+    let warnings = lint(source, Some(&dir.join("main.co")));
+    let file = Some(dir.join("data.co").to_string_lossy().to_string());
+    std::fs::remove_dir_all(&dir).unwrap();
+    let warnings: Vec<(Option<String>, usize, String)> = warnings
+        .unwrap()
+        .into_iter()
+        .map(|w| (w.location.file, w.location.line, w.message))
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            (
+                file.clone(),
+                3,
+                "value 5000 of BIG does not fit in 10 bits and is stored as 904".to_string()
+            ),
+            (file, 4, "variable SPARE is never used".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn examples_have_no_warnings() {
+    for entry in std::fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|e| e.to_str()) != Some("co") {
+            continue;
+        }
+        let source = std::fs::read_to_string(&path).unwrap();
+        let warnings = lint(&source, Some(&path)).unwrap();
+        assert!(warnings.is_empty(), "{}: {:?}", path.display(), warnings);
+    }
+}
@@ -0,0 +1,62 @@
+use copper::enums::{Instruction, MNEMONICS};
+use copper::program::Program;
+
+// a macro cannot take the name of an instruction, which it would replace
+#[test]
+fn macro_cannot_be_an_instruction() {
+    for name in MNEMONICS.iter().chain(["MOV", "SWAP"].iter()) {
+        let source = format!("#CODE\n%macro {} r\n    LDA r 7\n%endmacro\n", name);
+        let error = match Program::from_source(&source) {
+            Ok(_) => panic!("%macro {} is accepted", name),
+            Err(error) => error,
+        };
+        assert!(
+            error.message.contains("cannot be a macro"),
+            "{}",
+            error.message
+        );
+    }
+}
+
+// a macro of an included file called between a global label and its local labels
+// keeps them in the scope of the global label
+#[test]
+fn included_macro_keeps_the_scope_of_local_labels() {
+    let dir = std::env::temp_dir().join(format!("copper-scope-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("lib.co"),
+        "#CODE\n%macro TWICE r\n    INC r\n    INC r\n%endmacro\n",
+    )
+    .unwrap();
+    let source = "%include \"lib.co\"
+#CODE
+FIRST:
+    LDA T0 0
+    TWICE T0
+.loop:
+    DEC T0
+    BNE T0 0 .loop
+SECOND:
+    LDA T1 3
+    TWICE T1
+.loop:
+    DEC T1
+    BNE T1 0 .loop
+    OUT T1
+";
+    let path = dir.join("main.co");
+    let mut program = Program::new();
+    let loaded = program.load_source_at(source, &path);
+    std::fs::remove_dir_all(&dir).unwrap();
+    loaded.unwrap();
+    let names: Vec<String> = program
+        .instructions
+        .iter()
+        .filter_map(|i| match i {
+            Instruction::LABEL(label) => Some(program.label_name(label)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(names, ["FIRST", "FIRSTloop", "SECOND", "SECONDloop"]);
+}
@@ -0,0 +1,107 @@
+use copper::io::QueueIo;
+use copper::program::{ProgramBuilder, RunOutcome};
+
+// output of a program, its outcome and the messages of the optimizer
+fn run(source: &str, optimize: bool, strict: bool) -> (String, RunOutcome, Vec<String>) {
+    let io = QueueIo::new(&[]);
+    let mut program = ProgramBuilder::new()
+        .optimize(optimize)
+        .strict(strict)
+        .max_steps(100_000)
+        .io(Box::new(io.clone()))
+        .build();
+    program.load_source(source).unwrap();
+    let outcome = program.run();
+    let messages = program
+        .optimizations
+        .iter()
+        .map(|c| c.message.clone())
+        .collect();
+    (io.output(), outcome, messages)
+}
+
+// the program prints the same thing once optimized, with the changes that were made
+fn optimized(source: &str) -> Vec<String> {
+    let (output, outcome, messages) = run(source, false, false);
+    assert!(messages.is_empty());
+    let (optimized_output, optimized_outcome, messages) = run(source, true, false);
+    assert_eq!(
+        (output, outcome),
+        (optimized_output, optimized_outcome),
+        "{}",
+        source
+    );
+    messages
+}
+
+#[test]
+fn removes_self_loads() {
+    let messages = optimized("#CODE\nLDA T0 4\nLDA T0 T0\nOUT T0\nHLT\n");
+    assert_eq!(messages, ["removed LDA T0 T0, which does not change T0"]);
+}
+
+// `LDA T1 T1` traps in strict mode when T1 was never written, so it is kept
+#[test]
+fn keeps_self_loads_in_strict_mode() {
+    let source = "#CODE\nLDA T1 T1\nHLT\n";
+    let (_, outcome, messages) = run(source, true, true);
+    assert!(messages.is_empty(), "{:?}", messages);
+    assert!(matches!(outcome, RunOutcome::Trap { .. }), "{:?}", outcome);
+}
+
+#[test]
+fn merges_increments() {
+    let messages = optimized("#CODE\nLDA T0 1\nINC T0\nINC T0\nINC T0\nOUT T0\nHLT\n");
+    assert_eq!(messages, ["merged 3 INC T0 into ADD T0 3"]);
+}
+
+// the merged increments fit in the constants of the machine
+#[test]
+fn merged_increments_fit_in_a_constant() {
+    let source = format!(
+        "#CONFIG small\n#CODE\n{}OUT T0\nHLT\n",
+        "INC T0\n".repeat(300)
+    );
+    let messages = optimized(&source);
+    assert_eq!(
+        messages,
+        [
+            "merged 255 INC T0 into ADD T0 255",
+            "merged 45 INC T0 into ADD T0 45"
+        ]
+    );
+}
+
+#[test]
+fn threads_jumps() {
+    let source = "#CODE
+    LDA T0 1
+    BEQ T0 1 first
+    OUT T0
+first:
+    JMP second
+    OUT T0
+second:
+    LDA T1 7
+    OUT T1
+    HLT
+";
+    let messages = optimized(source);
+    assert!(
+        messages.contains(&"BEQ to first now goes directly to second".to_string()),
+        "{:?}",
+        messages
+    );
+}
+
+#[test]
+fn removes_jumps_to_the_next_instruction() {
+    let messages = optimized("#CODE\nLDA T0 2\nJMP next\nnext:\nOUT T0\nHLT\n");
+    assert_eq!(messages, ["removed JMP next to the next instruction"]);
+}
+
+#[test]
+fn removes_dead_code() {
+    let messages = optimized("#CODE\nLDA T0 2\nOUT T0\nHLT\nOUT T0\nINC T0\n");
+    assert_eq!(messages, ["removed 2 unreachable instruction(s) after HLT"]);
+}
@@ -0,0 +1,85 @@
+use copper::disasm::disassemble;
+use copper::file::{export, LineType};
+use copper::io::QueueIo;
+use copper::program::{Program, RunOutcome};
+
+fn bytes(source: &str) -> Vec<u8> {
+    let bits = export(LineType::from_text(source)).unwrap();
+    bits.as_bytes()
+        .chunks(8)
+        .map(|chunk| u8::from_str_radix(std::str::from_utf8(chunk).unwrap(), 2).unwrap())
+        .collect()
+}
+
+fn output(mut program: Program) -> (String, RunOutcome) {
+    let io = QueueIo::new(&[]);
+    program.io = Box::new(io.clone());
+    let outcome = program.run();
+    (io.output(), outcome)
+}
+
+// output of a program run from its source, which must be the same from its binary file
+fn run(source: &str) -> (String, RunOutcome) {
+    let output = self::output(Program::from_source(source).unwrap());
+    let binary = self::output(Program::from_bytes(&bytes(source)).unwrap());
+    assert_eq!(output, binary, "{}", source);
+    output
+}
+
+#[test]
+fn formats() {
+    let source = "#CODE
+    LDA T0 42
+    OUT T0
+    OUT T0 dec
+    OUT T0 hex
+    OUT T0 bin
+    OUT T0 signed
+    OUT T0 char
+    OUT 10 char
+    HLT
+";
+    assert_eq!(
+        run(source),
+        (
+            "42\n42\n0x2A\n0b101010\n42\n*\n".to_string(),
+            RunOutcome::Halted(0)
+        )
+    );
+}
+
+// the sign bit is the one of the word of the machine
+#[test]
+fn signed_values() {
+    let source = "#CODE\nLDA T0 0\nNOT T0\nOUT T0 signed\nOUT T0 hex\nHLT\n";
+    assert_eq!(run(source).0, "-1\n0xFFFFFFFF\n");
+    let source = format!("#CONFIG classic64\n{}", source);
+    let (output, _) = output(Program::from_source(&source).unwrap());
+    assert_eq!(output, "-1\n0xFFFFFFFFFFFFFFFF\n");
+}
+
+#[test]
+fn invalid_character_is_a_trap() {
+    let source = "#CODE\nLDA T0 0\nNOT T0\nOUT T0 char\nHLT\n";
+    assert_eq!(
+        run(source).1,
+        RunOutcome::Trap {
+            index: 2,
+            message: "Invalid character".to_string()
+        }
+    );
+}
+
+#[test]
+fn formats_are_disassembled() {
+    let source = "#CODE\nOUT T0 hex\nOUT T1 bin\nOUT T2 signed\nOUT T3\nHLT\n";
+    let text = disassemble(LineType::from_bytes(&bytes(source)).unwrap()).unwrap();
+    for line in [
+        "OUT T0 hex\n",
+        "OUT T1 bin\n",
+        "OUT T2 signed\n",
+        "OUT T3\n",
+    ] {
+        assert!(text.contains(line), "{}", text);
+    }
+}
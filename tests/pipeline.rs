@@ -0,0 +1,86 @@
+use copper::enums::Register;
+use copper::io::QueueIo;
+use copper::pipeline::{Pipeline, PipelineStats};
+use copper::program::{Program, ProgramBuilder, RunOutcome};
+use std::path::Path;
+
+fn program(source: &str, io: &QueueIo) -> Program {
+    let mut program = ProgramBuilder::new()
+        .max_steps(1_000_000)
+        .io(Box::new(io.clone()))
+        .build();
+    program.load_source(source).unwrap();
+    program
+}
+
+fn stats(source: &str, forwarding: bool) -> PipelineStats {
+    let io = QueueIo::new(&[]);
+    let mut program = program(source, &io);
+    let mut pipeline = Pipeline::new(forwarding);
+    pipeline.diagram = false;
+    assert_eq!(pipeline.run(&mut program), RunOutcome::Finished);
+    pipeline.stats
+}
+
+// independent instructions leave the pipeline one per cycle once it is full
+#[test]
+fn no_hazards() {
+    let stats = stats("#CODE\nLDA T0 1\nLDA T1 2\nLDA T2 3\n", false);
+    assert_eq!((stats.cycles, stats.instructions), (7, 3));
+    assert_eq!((stats.data_stalls, stats.control_flushes), (0, 0));
+}
+
+#[test]
+fn data_hazards() {
+    let source = "#CODE\nLDA T0 1\nADD T0 1\n";
+    let stats = self::stats(source, false);
+    assert_eq!((stats.cycles, stats.data_stalls), (8, 2));
+    let stats = self::stats(source, true);
+    assert_eq!((stats.cycles, stats.data_stalls), (6, 0));
+    // a value loaded from memory is only forwarded after MEM
+    let stats = self::stats("#DATA\nX 1\n#CODE\nLDA T0 X\nADD T0 1\n", true);
+    assert_eq!((stats.cycles, stats.data_stalls), (7, 1));
+}
+
+// branches are resolved in EX and jumps in ID, the fetched instructions are flushed
+#[test]
+fn control_hazards() {
+    let stats = stats(
+        "#CODE\nLDA T0 1\nBEQ T0 1 end\nLDA T1 5\nend:\nLDA T2 3\n",
+        true,
+    );
+    assert_eq!((stats.cycles, stats.instructions), (9, 3));
+    assert_eq!(stats.control_flushes, 2);
+    let stats = self::stats("#CODE\nJMP end\nLDA T1 5\nend:\nLDA T2 3\n", true);
+    assert_eq!((stats.cycles, stats.instructions), (7, 2));
+    assert_eq!(stats.control_flushes, 1);
+}
+
+// the pipelined examples print, end and leave the registers as with `Program::run`
+#[test]
+fn examples_match_the_program() {
+    for name in ["arrays", "factorial", "hello"] {
+        let path = Path::new("examples").join(format!("{}.co", name));
+        let source = std::fs::read_to_string(&path).unwrap();
+        let run = |pipelined: Option<bool>| {
+            let io = QueueIo::new(&[5, 3, 7]);
+            let mut program = program(&source, &io);
+            let outcome = match pipelined {
+                Some(forwarding) => {
+                    let mut pipeline = Pipeline::new(forwarding);
+                    pipeline.diagram = false;
+                    pipeline.run(&mut program)
+                }
+                None => program.run(),
+            };
+            let registers: Vec<u64> = [Register::T0, Register::T1, Register::T2, Register::T3]
+                .iter()
+                .map(|r| program.registers()[r])
+                .collect();
+            (io.output(), outcome, registers, program.stack().to_vec())
+        };
+        let expected = run(None);
+        assert_eq!(run(Some(false)), expected, "{}", name);
+        assert_eq!(run(Some(true)), expected, "{}", name);
+    }
+}
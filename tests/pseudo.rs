@@ -0,0 +1,119 @@
+use copper::enums::Register;
+use copper::program::{Program, RunOutcome};
+use copper::pseudo::expand;
+
+fn expansion(line: &str) -> Vec<String> {
+    expand(line).unwrap().unwrap()
+}
+
+// registers of a program run to its end, with the stack it leaves
+fn run(source: &str) -> (Vec<u64>, Vec<u64>) {
+    let mut program = Program::from_source(source).unwrap();
+    assert_eq!(program.run(), RunOutcome::Finished, "{}", source);
+    let registers = [Register::T0, Register::T1, Register::T2, Register::T3]
+        .iter()
+        .map(|r| program.registers()[r])
+        .collect();
+    (registers, program.stack().to_vec())
+}
+
+#[test]
+fn expansions() {
+    assert_eq!(expansion("MOV T0 X"), ["LDA T0 X"]);
+    assert_eq!(expansion("CLR T1"), ["LDA T1 0"]);
+    assert_eq!(
+        expansion("BGE T0 5 done"),
+        ["BSM 5 T0 done", "BEQ T0 5 done"]
+    );
+    assert_eq!(
+        expansion("BLE T0 5 done"),
+        ["BBG 5 T0 done", "BEQ T0 5 done"]
+    );
+    assert_eq!(expansion("BZ T2 done"), ["BEQ T2 0 done"]);
+    assert_eq!(expansion("BNZ T2 done"), ["BNE T2 0 done"]);
+    assert_eq!(expansion("SWAP T0 T1"), ["PUSH T0", "LDA T0 T1", "POP T1"]);
+    assert_eq!(expansion("NEG T3"), ["NOT T3", "INC T3"]);
+    assert_eq!(
+        expansion("XOR T0 T1 ; comment"),
+        [
+            "PUSH T2",
+            "LDA T2 T0",
+            "AND T2 T1",
+            "NOT T2",
+            "OR T0 T1",
+            "AND T0 T2",
+            "POP T2"
+        ]
+    );
+    assert_eq!(expansion("XOR T1 T1"), ["LDA T1 0"]);
+    assert_eq!(expand("LDA T0 1").unwrap(), None);
+}
+
+#[test]
+fn invalid_operands() {
+    assert_eq!(
+        expand("SWAP T0 X").unwrap_err(),
+        "SWAP expects registers, found X"
+    );
+    assert_eq!(
+        expand("NEG 4").unwrap_err(),
+        "NEG expects registers, found 4"
+    );
+    assert_eq!(expand("BZ T0").unwrap_err(), "Invalid operands for BZ");
+}
+
+#[test]
+fn moves_and_swaps() {
+    let (registers, stack) = run("#DATA\nX 9\n#CODE\nMOV T0 X\nMOV T1 4\nSWAP T0 T1\nCLR T2\n");
+    assert_eq!(registers[..3], [4, 9, 0]);
+    assert!(stack.is_empty());
+}
+
+#[test]
+fn negation_is_the_twos_complement() {
+    let (registers, _) = run("#CODE\nLDA T0 5\nNEG T0\nADD T0 7\n");
+    assert_eq!(registers[0], 2);
+}
+
+// each branch sets T1 when it is taken
+#[test]
+fn branches() {
+    let cases = [
+        ("BGE T0 3", 3, 1),
+        ("BGE T0 3", 4, 1),
+        ("BGE T0 3", 2, 0),
+        ("BLE T0 3", 3, 1),
+        ("BLE T0 3", 2, 1),
+        ("BLE T0 3", 4, 0),
+        ("BZ T0", 0, 1),
+        ("BZ T0", 1, 0),
+        ("BNZ T0", 1, 1),
+        ("BNZ T0", 0, 0),
+    ];
+    for (branch, value, taken) in cases {
+        let source = format!(
+            "#CODE\nLDA T0 {}\n{} taken\nJMP end\ntaken:\nLDA T1 1\nend:\n",
+            value, branch
+        );
+        let (registers, _) = run(&source);
+        assert_eq!(registers[1], taken, "{} with T0 = {}", branch, value);
+    }
+}
+
+// XOR keeps its second operand, the register it computes in and the stack
+#[test]
+fn exclusive_or() {
+    for (a, b) in [(0, 0), (12, 10), (1023, 5), (7, 7)] {
+        let source = format!(
+            "#CODE\nLDA T0 {}\nLDA T1 {}\nLDA T2 99\nPUSH 42\nXOR T0 T1\n",
+            a, b
+        );
+        let (registers, stack) = run(&source);
+        assert_eq!(registers[..3], [a ^ b, b, 99], "{} ^ {}", a, b);
+        assert_eq!(stack, [42]);
+    }
+    let (registers, _) = run("#CODE\nLDA T2 6\nLDA T1 3\nLDA T0 1\nXOR T2 T1\n");
+    assert_eq!(registers[..3], [1, 3, 5]);
+    let (registers, _) = run("#CODE\nLDA T3 6\nXOR T3 T3\n");
+    assert_eq!(registers[3], 0);
+}
@@ -0,0 +1,98 @@
+use copper::program::Program;
+use copper::stack::{analyze, StackAnalysis};
+use std::process::Command;
+
+fn analysis(source: &str) -> (Program, StackAnalysis) {
+    let program = Program::from_source(source).unwrap();
+    let analysis = analyze(&program);
+    (program, analysis)
+}
+
+// depths before each instruction, as printed in the report
+fn depths(analysis: &StackAnalysis) -> Vec<String> {
+    analysis
+        .depths
+        .iter()
+        .map(|d| d.map_or("-".to_string(), |d| d.to_string()))
+        .collect()
+}
+
+#[test]
+fn balanced_code() {
+    let (program, analysis) = analysis("#CODE\nPUSH 1\nPUSH 2\nPOP T0\nPOP T1\nHLT\n");
+    assert_eq!(depths(&analysis), ["0", "1", "2", "1", "0"]);
+    assert_eq!(analysis.max_depth, Some(2));
+    assert!(analysis.warnings(&program).is_empty());
+    assert!(analysis
+        .report(&program)
+        .ends_with("Maximum stack depth: 2\n"));
+}
+
+// the loop pushes and pops once per iteration
+#[test]
+fn balanced_loop() {
+    let source = "#CODE\nLDA T0 0\nloop:\nPUSH T0\nPOP T0\nINC T0\nBNE T0 5 loop\nHLT\n";
+    let (program, analysis) = analysis(source);
+    assert_eq!(analysis.max_depth, Some(1));
+    assert!(analysis.warnings(&program).is_empty());
+}
+
+// the POP runs on an empty stack when the branch skips the PUSH
+#[test]
+fn underflow_on_a_path() {
+    let source = "#CODE\nLDA T0 1\nBEQ T0 0 skip\nPUSH 1\nskip:\nPOP T0\nHLT\n";
+    let (program, analysis) = analysis(source);
+    assert_eq!(depths(&analysis), ["0", "0", "0", "0..1", "0..1", "0"]);
+    assert_eq!(analysis.underflows.len(), 1);
+    assert_eq!(analysis.underflows[0].index, 4);
+    assert_eq!(analysis.underflows[0].path, [0, 3]);
+    let warnings = analysis.warnings(&program);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].0.line, 6);
+    assert_eq!(
+        warnings[0].1,
+        "POP can run on an empty stack, through the lines 2, 5"
+    );
+}
+
+// nothing runs after a POP on a stack that is always empty
+#[test]
+fn pop_on_an_empty_stack() {
+    let (_, analysis) = analysis("#CODE\nPOP T0\nHLT\n");
+    assert_eq!(depths(&analysis), ["0", "-"]);
+    assert_eq!(analysis.underflows[0].path, [0]);
+}
+
+#[test]
+fn unbounded_loop() {
+    let (program, analysis) = analysis("#CODE\nloop:\nPUSH 1\nJMP loop\n");
+    assert_eq!(analysis.max_depth, None);
+    assert_eq!(analysis.unbounded, [0]);
+    assert_eq!(
+        analysis.warnings(&program)[0].1,
+        "the stack grows at each iteration of this loop"
+    );
+    assert!(analysis
+        .report(&program)
+        .ends_with("Maximum stack depth: unbounded\n"));
+}
+
+// the report goes to stdout and the warnings make the command fail
+#[test]
+fn stack_command() {
+    let status = |name: &str, source: &str| {
+        let path =
+            std::env::temp_dir().join(format!("copper-stack-{}-{}.co", name, std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_copper"))
+            .arg("stack")
+            .arg(&path)
+            .output()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("Maximum stack depth"));
+        output.status.code().unwrap()
+    };
+    assert_eq!(status("balanced", "#CODE\nPUSH 1\nPOP T0\nHLT\n"), 0);
+    assert_eq!(status("underflow", "#CODE\nPOP T0\nHLT\n"), 5);
+}
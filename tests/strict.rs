@@ -0,0 +1,87 @@
+use copper::enums::Register;
+use copper::program::{Program, ProgramBuilder, RunOutcome};
+use std::process::Command;
+
+fn program(source: &str, strict: bool) -> Program {
+    let mut program = ProgramBuilder::new().strict(strict).build();
+    program.load_source(source).unwrap();
+    program
+}
+
+fn trap(source: &str) -> (usize, String) {
+    match program(source, true).run() {
+        RunOutcome::Trap { index, message } => (index, message),
+        outcome => panic!("{} ended with {:?}", source, outcome),
+    }
+}
+
+// the registers and the memory cells that were never written hold 0
+#[test]
+fn unwritten_values_are_zero() {
+    let source = "#DATA\nA 1\n#CODE\nADD T0 1\nLDA T1 A+3\nFADD F0 F1\nFTOI T2 F0\n";
+    let mut program = program(source, false);
+    assert_eq!(program.run(), RunOutcome::Finished);
+    assert_eq!(program.registers()[&Register::T0], 1);
+    assert_eq!(program.registers()[&Register::T1], 0);
+    assert_eq!(program.registers()[&Register::T2], 0);
+}
+
+#[test]
+fn strict_reads_trap() {
+    assert_eq!(
+        trap("#CODE\nLDA T0 1\nADD T0 T1\n"),
+        (1, "T1 is read before being written".to_string())
+    );
+    assert_eq!(
+        trap("#CODE\nINC T2\n"),
+        (0, "T2 is read before being written".to_string())
+    );
+    assert_eq!(
+        trap("#CODE\nFLDA F0 1\nFADD F0 F3\n"),
+        (1, "F3 is read before being written".to_string())
+    );
+    let (index, message) = trap("#DATA\nA 1\n#CODE\nLDA T0 A\nLDA T1 A+3\n");
+    assert_eq!(index, 1);
+    assert!(
+        message.ends_with("is read before being written"),
+        "{}",
+        message
+    );
+}
+
+// the values written by the program or declared in #DATA can be read
+#[test]
+fn strict_accepts_written_values() {
+    let source = "#DATA\nA 1\n#CODE\nLDA T0 A\nSTR A+1 T0\nLDA T1 A+1\nPUSH T1\nPOP T3\n";
+    let mut program = program(source, true);
+    assert_eq!(program.run(), RunOutcome::Finished);
+    assert_eq!(program.registers()[&Register::T3], 1);
+}
+
+// the trap is reported with the line of the source and the instruction
+#[test]
+fn strict_option() {
+    let path = std::env::temp_dir().join(format!("copper-strict-{}.co", std::process::id()));
+    std::fs::write(&path, "#CODE\nLDA T0 1\nADD T0 T3\nOUT T0\n").unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_copper"))
+            .arg("run")
+            .args(args)
+            .arg(&path)
+            .output()
+            .unwrap()
+    };
+    let output = run(&[]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    let output = run(&["--strict"]);
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("line 3 (ADD T0 T3)"), "{}", stderr);
+    assert!(
+        stderr.contains("T3 is read before being written"),
+        "{}",
+        stderr
+    );
+    std::fs::remove_file(path).unwrap();
+}
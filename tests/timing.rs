@@ -0,0 +1,87 @@
+use copper::program::{ProgramBuilder, RunOutcome};
+use copper::timing::{CostModel, CycleStats};
+use std::process::Command;
+
+fn stats(source: &str, cost_model: CostModel) -> CycleStats {
+    let mut program = ProgramBuilder::new().cost_model(cost_model).build();
+    program.load_source(source).unwrap();
+    assert_eq!(program.run(), RunOutcome::Halted(0));
+    program.stats
+}
+
+// LDA reads memory, BEQ is taken over OUT, the label costs nothing
+const PROGRAM: &str = "#DATA
+X 3
+#CODE
+    LDA T0 X
+    ADD T0 1
+    BEQ T0 4 end
+    OUT T0
+end:
+    HLT
+";
+
+#[test]
+fn default_costs() {
+    let stats = stats(PROGRAM, CostModel::default());
+    assert_eq!((stats.cycles, stats.instructions), (8, 4));
+    assert_eq!(stats.cpi(), 2.0);
+    assert_eq!(stats.per_opcode["LDA"], (1, 3));
+    assert_eq!(stats.per_opcode["ADD"], (1, 1));
+    assert_eq!(stats.per_opcode["BEQ"], (1, 3));
+    assert!(!stats.per_opcode.contains_key("OUT"));
+    assert!(!stats.per_opcode.contains_key("LABEL"));
+}
+
+// a branch that is not taken has no penalty
+#[test]
+fn branch_penalty_only_when_taken() {
+    let stats = stats(
+        "#CODE\nLDA T0 1\nBEQ T0 2 end\nend:\nHLT\n",
+        CostModel::default(),
+    );
+    assert_eq!(stats.per_opcode["BEQ"], (1, 1));
+    assert_eq!(stats.cycles, 3);
+}
+
+#[test]
+fn cost_table() {
+    let table = "; costs of the course\nadd 4\nMEMORY 0\nBRANCH 5\n\nPUTC 9 ; characters\n";
+    let model: CostModel = table.parse().unwrap();
+    let stats = stats(PROGRAM, model.clone());
+    assert_eq!(stats.per_opcode["LDA"], (1, 1));
+    assert_eq!(stats.per_opcode["ADD"], (1, 4));
+    assert_eq!(stats.per_opcode["BEQ"], (1, 6));
+    let stats = self::stats("#CODE\nLDA T0 65\nPUTC T0\nOUT T0\nHLT\n", model);
+    assert_eq!(stats.per_opcode["OUT"], (2, 18));
+}
+
+#[test]
+fn invalid_cost_tables() {
+    assert_eq!(
+        "ADD 1\nFOO 3\n".parse::<CostModel>().unwrap_err(),
+        "line 2: unknown opcode FOO"
+    );
+    assert_eq!(
+        "ADD many\n".parse::<CostModel>().unwrap_err(),
+        "line 1: expected a number of cycles"
+    );
+}
+
+// the report of --stats goes to stderr, stdout only holds the output of the program
+#[test]
+fn stats_are_reported_on_stderr() {
+    let path = std::env::temp_dir().join(format!("copper-timing-{}.co", std::process::id()));
+    std::fs::write(&path, PROGRAM.replace("4 end", "0 end")).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_copper"))
+        .arg("run")
+        .arg("--stats")
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "4\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Cycles: 11\n"), "{}", stderr);
+    assert!(stderr.contains("CPI: 2.20\n"), "{}", stderr);
+}